@@ -0,0 +1,153 @@
+use jsonc_parser::ast::Value;
+
+/// Serializes the value the same way Jupyter writes notebooks, which is
+/// `json.dumps(nb, sort_keys=True, indent=1, ensure_ascii=False)` followed
+/// by a trailing newline.
+pub fn to_canonical_json(value: &Value) -> String {
+  let mut text = String::new();
+  write_value(value, 0, &mut text);
+  text.push('\n');
+  text
+}
+
+fn write_value(value: &Value, indent_level: usize, text: &mut String) {
+  match value {
+    Value::StringLit(lit) => write_string(&lit.value, text),
+    Value::NumberLit(lit) => write_number(lit.value, text),
+    Value::BooleanLit(lit) => text.push_str(if lit.value { "true" } else { "false" }),
+    Value::NullKeyword(_) => text.push_str("null"),
+    Value::Array(array) => {
+      if array.elements.is_empty() {
+        text.push_str("[]");
+        return;
+      }
+      text.push('[');
+      for (i, element) in array.elements.iter().enumerate() {
+        if i > 0 {
+          text.push(',');
+        }
+        write_newline(indent_level + 1, text);
+        write_value(element, indent_level + 1, text);
+      }
+      write_newline(indent_level, text);
+      text.push(']');
+    }
+    Value::Object(obj) => {
+      if obj.properties.is_empty() {
+        text.push_str("{}");
+        return;
+      }
+      // like python, the last duplicate property wins
+      let mut properties = obj
+        .properties
+        .iter()
+        .enumerate()
+        .map(|(i, prop)| (prop.name.as_str(), i, &prop.value))
+        .collect::<Vec<_>>();
+      properties.sort_by(|a, b| a.0.cmp(b.0).then(b.1.cmp(&a.1)));
+      properties.dedup_by(|a, b| a.0 == b.0);
+      text.push('{');
+      for (i, (name, _, value)) in properties.into_iter().enumerate() {
+        if i > 0 {
+          text.push(',');
+        }
+        write_newline(indent_level + 1, text);
+        write_string(name, text);
+        text.push_str(": ");
+        write_value(value, indent_level + 1, text);
+      }
+      write_newline(indent_level, text);
+      text.push('}');
+    }
+  }
+}
+
+fn write_newline(indent_level: usize, text: &mut String) {
+  text.push('\n');
+  for _ in 0..indent_level {
+    text.push(' ');
+  }
+}
+
+fn write_string(value: &str, text: &mut String) {
+  // serde_json escapes the same characters as python's json module with ensure_ascii=False
+  text.push_str(&serde_json::to_string(value).unwrap());
+}
+
+fn write_number(value: &str, text: &mut String) {
+  // convert the number syntax allowed when parsing to valid json
+  let (is_negative, digits) = match value.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, value.trim_start_matches('+')),
+  };
+  let hex_digits = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X"));
+  if is_negative {
+    text.push('-');
+  }
+  match hex_digits {
+    Some(hex_digits) => match u64::from_str_radix(hex_digits, 16) {
+      Ok(hex_value) => text.push_str(&hex_value.to_string()),
+      // too large for a u64, so write it as a float like json parsers read large numbers
+      Err(_) => {
+        let hex_value = hex_digits
+          .chars()
+          .filter_map(|c| c.to_digit(16))
+          .fold(0f64, |value, digit| value * 16.0 + digit as f64);
+        write_float(hex_value, text);
+      }
+    },
+    None if digits.contains(['.', 'e', 'E']) => match digits.parse::<f64>() {
+      Ok(value) => write_float(value, text),
+      Err(_) => text.push_str(digits),
+    },
+    None => text.push_str(digits),
+  }
+}
+
+/// Writes the float the way python's `repr` does (ex. `1e5` is `100000.0`
+/// and `1e16` is `1e+16`).
+fn write_float(value: f64, text: &mut String) {
+  if value.is_infinite() {
+    text.push_str("Infinity");
+    return;
+  }
+  // rust's debug output is the shortest round-tripping representation and switches
+  // to an exponent at the same thresholds as python, but writes the exponent differently
+  let repr = format!("{:?}", value);
+  match repr.split_once('e') {
+    Some((mantissa, exponent)) => {
+      let (sign, exponent) = match exponent.strip_prefix('-') {
+        Some(exponent) => ('-', exponent),
+        None => ('+', exponent),
+      };
+      text.push_str(&format!("{mantissa}e{sign}{exponent:0>2}"));
+    }
+    None => text.push_str(&repr),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn canonical(text: &str) -> String {
+    let result = jsonc_parser::parse_to_ast(text, &Default::default(), &Default::default()).unwrap();
+    to_canonical_json(&result.value.unwrap())
+  }
+
+  #[test]
+  fn writes_canonical_json() {
+    assert_eq!(canonical("{}"), "{}\n");
+    assert_eq!(
+      canonical("{\"b\": [1, +2, 0x1F, -0x10], \"a\": {\"x\": [], \"y\": {}}}"),
+      "{\n \"a\": {\n  \"x\": [],\n  \"y\": {}\n },\n \"b\": [\n  1,\n  2,\n  31,\n  -16\n ]\n}\n"
+    );
+    assert_eq!(canonical("[\"\\u00e9\\u001f\\t\\/\"]"), "[\n \"é\\u001f\\t/\"\n]\n");
+    assert_eq!(canonical("{\"a\": 1, \"a\": 2}"), "{\n \"a\": 2\n}\n");
+    assert_eq!(canonical("[0x1FFFFFFFFFFFFFFFFF]"), "[\n 5.902958103587057e+20\n]\n");
+    assert_eq!(
+      canonical("[1e5, 1.50, -0.0, 1E16, 1.5e-7, 0.0001, 12345678901234567890, 1e999]"),
+      "[\n 100000.0,\n 1.5,\n -0.0,\n 1e+16,\n 1.5e-07,\n 0.0001,\n 12345678901234567890,\n Infinity\n]\n"
+    );
+  }
+}
@@ -0,0 +1,100 @@
+use crate::source_map::LineAndColumn;
+
+/// An error a plugin returned when formatting a cell.
+pub struct CellFormatError {
+  /// Index of the cell in the notebook.
+  pub index: usize,
+  pub id: Option<String>,
+  pub language: String,
+  /// Position of the error in the notebook when the plugin reported one.
+  pub position: Option<LineAndColumn>,
+  pub error: anyhow::Error,
+}
+
+impl std::fmt::Display for CellFormatError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Error formatting cell at index {}", self.index)?;
+    if let Some(id) = &self.id {
+      write!(f, " (id: {}, language: {})", id, self.language)?;
+    } else {
+      write!(f, " (language: {})", self.language)?;
+    }
+    if let Some(position) = &self.position {
+      write!(f, " at line {}, column {}", position.line, position.column)?;
+    }
+    write!(f, ": {:#}", self.error)
+  }
+}
+
+/// Combines the errors of all the cells into a single error.
+pub fn combine_cell_errors(errors: Vec<CellFormatError>) -> anyhow::Error {
+  let message = errors
+    .iter()
+    .map(|error| error.to_string())
+    .collect::<Vec<_>>()
+    .join("\n\n");
+  anyhow::anyhow!("{}", message)
+}
+
+/// Finds the position a plugin reported in its error message
+/// (ex. `Line 2, column 5: ...` or `... at file:///code_block.ts:2:5`).
+pub fn find_error_position(message: &str) -> Option<LineAndColumn> {
+  find_line_column_text_position(message).or_else(|| find_file_path_position(message))
+}
+
+fn find_line_column_text_position(message: &str) -> Option<LineAndColumn> {
+  let lower_message = message.to_ascii_lowercase();
+  lower_message.match_indices("line ").find_map(|(index, text)| {
+    if lower_message[..index].ends_with(|c: char| c.is_ascii_alphabetic()) {
+      return None;
+    }
+    let (line, rest) = take_number(&lower_message[index + text.len()..])?;
+    let rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    let rest = rest.strip_prefix("column ").or_else(|| rest.strip_prefix("col "))?;
+    let (column, _) = take_number(rest)?;
+    Some(LineAndColumn { line, column })
+  })
+}
+
+fn find_file_path_position(message: &str) -> Option<LineAndColumn> {
+  message.match_indices("code_block.").find_map(|(index, text)| {
+    let rest = &message[index + text.len()..];
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+    let (line, rest) = take_number(rest.strip_prefix(':')?)?;
+    let (column, _) = take_number(rest.strip_prefix(':')?)?;
+    Some(LineAndColumn { line, column })
+  })
+}
+
+fn take_number(text: &str) -> Option<(usize, &str)> {
+  let end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+  let number = text[..end].parse::<usize>().ok().filter(|number| *number > 0)?;
+  Some((number, &text[end..]))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn position(line: usize, column: usize) -> LineAndColumn {
+    LineAndColumn { line, column }
+  }
+
+  #[test]
+  fn finds_error_position() {
+    assert_eq!(
+      find_error_position("Line 2, column 5: Expression expected"),
+      Some(position(2, 5))
+    );
+    assert_eq!(
+      find_error_position("Expected ';' at line 3 col 10"),
+      Some(position(3, 10))
+    );
+    assert_eq!(
+      find_error_position("Expression expected at file:///code_block.ts:4:12\n\n  let x = ;"),
+      Some(position(4, 12))
+    );
+    assert_eq!(find_error_position("Unexpected token on line 2"), None);
+    assert_eq!(find_error_position("Failed parsing."), None);
+  }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+
+use jsonc_parser::ast::Object;
+use jsonc_parser::ast::Value;
+use jsonc_parser::common::Ranged;
+
+use crate::text_changes::TextChange;
+
+/// nbformat 4 minor version that added cell ids.
+const CELL_IDS_NBFORMAT_MINOR: u64 = 5;
+
+/// Gets the text changes that give every cell of an nbformat 4 notebook a
+/// unique id, bumping `nbformat_minor` to 5 when the notebook is older.
+///
+/// Missing, invalid and duplicate ids are replaced with an id derived from
+/// the cell's position and source, so the same notebook always gets the same ids.
+pub fn get_cell_id_text_changes(file_text: &str, root_value: &Value) -> Vec<TextChange> {
+  let mut text_changes = Vec::new();
+  let Some(root_obj) = root_value.as_object() else {
+    return text_changes;
+  };
+  if root_obj.get_number("nbformat").map(|nbformat| nbformat.value) != Some("4") {
+    return text_changes;
+  }
+  let Some(cells) = root_obj.get_array("cells") else {
+    return text_changes;
+  };
+  if let Some(minor) = root_obj.get_number("nbformat_minor")
+    && minor
+      .value
+      .parse::<u64>()
+      .is_ok_and(|minor| minor < CELL_IDS_NBFORMAT_MINOR)
+  {
+    text_changes.push(TextChange {
+      range: minor.range.start..minor.range.end,
+      new_text: CELL_IDS_NBFORMAT_MINOR.to_string(),
+    });
+  }
+
+  let cells = cells
+    .elements
+    .iter()
+    .filter_map(|cell| cell.as_object())
+    .collect::<Vec<_>>();
+  // keep the first cell using each valid id
+  let mut used_ids = HashSet::new();
+  let kept_ids = cells
+    .iter()
+    .map(|cell| {
+      cell
+        .get_string("id")
+        .map(|id| id.value.as_ref())
+        .filter(|id| is_valid_cell_id(id) && used_ids.insert(id.to_string()))
+    })
+    .collect::<Vec<_>>();
+
+  for (index, (cell, kept_id)) in cells.iter().zip(kept_ids).enumerate() {
+    if kept_id.is_some() {
+      continue;
+    }
+    let source_text = cell
+      .get("source")
+      .map(|prop| &file_text[prop.value.start()..prop.value.end()])
+      .unwrap_or("");
+    let id = generate_cell_id(index, source_text, &used_ids);
+    used_ids.insert(id.clone());
+    text_changes.push(get_set_id_text_change(file_text, cell, &id));
+  }
+  text_changes
+}
+
+/// Gets the text change that replaces the cell's id or inserts it
+/// before the first property that sorts after it.
+fn get_set_id_text_change(file_text: &str, cell: &Object, id: &str) -> TextChange {
+  let id_text = serde_json::to_string(id).unwrap();
+  if let Some(prop) = cell.get("id") {
+    return TextChange {
+      range: prop.value.start()..prop.value.end(),
+      new_text: id_text,
+    };
+  }
+  let get_leading_whitespace = |pos: usize| {
+    let text = &file_text[..pos];
+    &text[text.trim_end().len()..]
+  };
+  let next_prop = cell.properties.iter().find(|prop| prop.name.as_str() > "id");
+  match (next_prop, cell.properties.last()) {
+    (Some(next_prop), _) => {
+      let whitespace = get_leading_whitespace(next_prop.range.start);
+      let separator = if whitespace.is_empty() { " " } else { whitespace };
+      TextChange {
+        range: next_prop.range.start..next_prop.range.start,
+        new_text: format!("\"id\": {},{}", id_text, separator),
+      }
+    }
+    (None, Some(last_prop)) => {
+      let whitespace = get_leading_whitespace(last_prop.range.start);
+      let separator = if whitespace.is_empty() { " " } else { whitespace };
+      TextChange {
+        range: last_prop.range.end..last_prop.range.end,
+        new_text: format!(",{}\"id\": {}", separator, id_text),
+      }
+    }
+    // empty object
+    (None, None) => TextChange {
+      range: cell.range.start + 1..cell.range.start + 1,
+      new_text: format!("\"id\": {}", id_text),
+    },
+  }
+}
+
+/// Generates an eight character hex id like Jupyter's from a hash of
+/// the cell's position and source.
+fn generate_cell_id(index: usize, source_text: &str, used_ids: &HashSet<String>) -> String {
+  let mut salt = 0u64;
+  loop {
+    // hash the index as a u64 so wasm32 generates the same ids as 64-bit targets
+    let hash = fnv1a_hash(&[
+      &(index as u64).to_le_bytes(),
+      &salt.to_le_bytes(),
+      source_text.as_bytes(),
+    ]);
+    let id = format!("{:016x}", hash)[..8].to_string();
+    if !used_ids.contains(&id) {
+      return id;
+    }
+    salt += 1;
+  }
+}
+
+/// 64-bit FNV-1a, which is used because it's stable across Rust versions.
+fn fnv1a_hash(parts: &[&[u8]]) -> u64 {
+  let mut hash = 0xcbf29ce484222325u64;
+  for byte in parts.iter().flat_map(|part| part.iter()) {
+    hash ^= *byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  hash
+}
+
+pub fn is_valid_cell_id(id: &str) -> bool {
+  (1..=64).contains(&id.len()) && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::text_changes::apply_text_changes;
+
+  fn fix_cell_ids(text: &str) -> String {
+    let result = jsonc_parser::parse_to_ast(text, &Default::default(), &Default::default()).unwrap();
+    let text_changes = get_cell_id_text_changes(text, &result.value.unwrap());
+    apply_text_changes(text, text_changes)
+  }
+
+  #[test]
+  fn fixes_cell_ids() {
+    let text = r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": null,
+   "id": "a",
+   "metadata": {},
+   "outputs": [],
+   "source": "x = 1"
+  },
+  {
+   "cell_type": "code",
+   "execution_count": null,
+   "id": "a",
+   "metadata": {},
+   "outputs": [],
+   "source": "x = 1"
+  },
+  {"cell_type": "markdown", "id": "not valid", "metadata": {}, "source": ""},
+  {"cell_type": "markdown", "metadata": {}, "source": ""},
+  {"cell_type": "raw"},
+  {}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 2
+}"#;
+    let fixed_text = fix_cell_ids(text);
+    assert_eq!(
+      fixed_text,
+      r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": null,
+   "id": "a",
+   "metadata": {},
+   "outputs": [],
+   "source": "x = 1"
+  },
+  {
+   "cell_type": "code",
+   "execution_count": null,
+   "id": "06079c8b",
+   "metadata": {},
+   "outputs": [],
+   "source": "x = 1"
+  },
+  {"cell_type": "markdown", "id": "1772abf3", "metadata": {}, "source": ""},
+  {"cell_type": "markdown", "id": "4773fdd2", "metadata": {}, "source": ""},
+  {"cell_type": "raw", "id": "4c27c899"},
+  {"id": "fd29b2d1"}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"#
+    );
+    // stable when run again
+    assert_eq!(fix_cell_ids(&fixed_text), fixed_text);
+    // not changed before nbformat 4
+    let text = r#"{"nbformat": 3, "nbformat_minor": 0, "worksheets": []}"#;
+    assert_eq!(fix_cell_ids(text), text);
+  }
+}
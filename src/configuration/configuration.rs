@@ -1,6 +1,125 @@
+use std::collections::BTreeMap;
+
+use dprint_core::configuration::ConfigKeyMap;
+use dprint_core::configuration::NewLineKind;
+use dprint_core::configuration::ParseConfigurationError;
+use dprint_core::generate_str_to_from;
 use serde::Deserialize;
 use serde::Serialize;
 
-#[derive(Default, Clone, Serialize, Deserialize)]
+/// How to handle a cell that a plugin fails to format.
+#[derive(Clone, PartialEq, Eq, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CellErrorBehavior {
+  /// Leave the cell as-is and continue formatting the other cells.
+  Skip,
+  /// Fail formatting the notebook with an error that names the cells.
+  Error,
+}
+
+generate_str_to_from![CellErrorBehavior, [Skip, "skip"], [Error, "error"]];
+
+/// How to resolve merge conflicts in the outputs and execution counts of cells.
+#[derive(Clone, PartialEq, Eq, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputConflictResolution {
+  /// Fail formatting the notebook with an error that names the conflicted cells.
+  Error,
+  /// Take the outputs from the current branch (`<<<<<<<` side).
+  Ours,
+  /// Take the outputs from the branch being merged (`>>>>>>>` side).
+  Theirs,
+}
+
+generate_str_to_from![
+  OutputConflictResolution,
+  [Error, "error"],
+  [Ours, "ours"],
+  [Theirs, "theirs"]
+];
+
+/// How to write the source of a cell when it's an array of lines.
+#[derive(Clone, PartialEq, Eq, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceArrayLayout {
+  /// Keep arrays written on one line on one line.
+  Maintain,
+  /// Write each line of the source on its own line.
+  Expand,
+}
+
+generate_str_to_from![SourceArrayLayout, [Maintain, "maintain"], [Expand, "expand"]];
+
+/// How to write a cell's source or output text, which can either
+/// be a string or an array of lines.
+#[derive(Clone, PartialEq, Eq, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MultilineStringStyle {
+  /// Keep the way it's written.
+  Preserve,
+  /// Write as an array of lines like Jupyter.
+  Array,
+  /// Write as a single string.
+  String,
+}
+
+generate_str_to_from![
+  MultilineStringStyle,
+  [Preserve, "preserve"],
+  [Array, "array"],
+  [String, "string"]
+];
+
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Configuration {}
+pub struct Configuration {
+  pub format_code_cells: bool,
+  pub format_markdown_cells: bool,
+  pub format_raw_cells: bool,
+  /// Formats the fenced code blocks in markdown cells through the plugin for their
+  /// language, even when markdown cells aren't formatted.
+  pub format_markdown_code_blocks: bool,
+  pub trim_trailing_newline: bool,
+  /// Language of code cells when the notebook's metadata doesn't specify one.
+  pub default_language: Option<String>,
+  /// Lowercase language id to whether cells in that language should be formatted.
+  pub languages: BTreeMap<String, bool>,
+  /// Lowercase language id to the file extension used to format it,
+  /// overriding the built-in mapping. `None` prevents formatting.
+  pub language_extensions: BTreeMap<String, Option<String>>,
+  /// Re-serializes the notebook in the layout Jupyter writes.
+  pub normalize_notebook: bool,
+  pub strip_outputs: bool,
+  pub strip_execution_counts: bool,
+  /// Removes cell metadata that changes when running or viewing a notebook.
+  pub strip_transient_metadata: bool,
+  /// Cells with this tag in their metadata are not formatted.
+  pub ignore_tag: String,
+  pub on_cell_error: CellErrorBehavior,
+  /// Lowercase language id to the configuration sent to the plugin
+  /// that formats cells in that language.
+  pub overrides: BTreeMap<String, ConfigKeyMap>,
+  /// File extensions of Jupytext percent scripts (ex. `"py"` or `"pct.py"`).
+  pub jupytext_extensions: Vec<String>,
+  /// File extensions of notebooks exported from Databricks (ex. `"py"` or `"sql"`).
+  pub databricks_extensions: Vec<String>,
+  /// Upgrades nbformat 3 notebooks to nbformat 4.
+  pub upgrade_notebook: bool,
+  /// Gives cells missing an id or using an invalid or duplicate id a new id,
+  /// upgrading the notebook to nbformat 4.5 when necessary.
+  pub fix_cell_ids: bool,
+  /// How to resolve merge conflicts that are only in outputs and execution counts.
+  pub output_conflicts: OutputConflictResolution,
+  /// Newline of the notebook's text. Newlines within cell sources are kept as-is.
+  pub new_line_kind: NewLineKind,
+  /// Indentation used when expanding a source array written on one line.
+  pub indent_width: u8,
+  pub use_tabs: bool,
+  pub source_array_layout: SourceArrayLayout,
+  pub source_style: MultilineStringStyle,
+  /// Style of the text of stream outputs and the multiline data of other outputs.
+  pub output_text_style: MultilineStringStyle,
+  /// Reports an error when the notebook or the formatted notebook doesn't
+  /// match the nbformat 4 schema.
+  pub validate_schema: bool,
+}
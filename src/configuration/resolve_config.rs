@@ -1,4 +1,10 @@
+use std::collections::BTreeMap;
+
+use super::CellErrorBehavior;
 use super::Configuration;
+use super::MultilineStringStyle;
+use super::OutputConflictResolution;
+use super::SourceArrayLayout;
 use dprint_core::configuration::*;
 
 /// Resolves configuration from a collection of key value strings.
@@ -24,12 +30,90 @@ use dprint_core::configuration::*;
 /// ```
 pub fn resolve_config(
   config: ConfigKeyMap,
-  _global_config: &GlobalConfiguration,
+  global_config: &GlobalConfiguration,
 ) -> ResolveConfigurationResult<Configuration> {
   let mut diagnostics = Vec::new();
+  let mut config = config;
 
   let resolved_config = Configuration {
-    // todo...
+    format_code_cells: get_value(&mut config, "formatCodeCells", true, &mut diagnostics),
+    format_markdown_cells: get_value(&mut config, "formatMarkdownCells", true, &mut diagnostics),
+    format_raw_cells: get_value(&mut config, "formatRawCells", true, &mut diagnostics),
+    format_markdown_code_blocks: get_value(&mut config, "formatMarkdownCodeBlocks", false, &mut diagnostics),
+    trim_trailing_newline: get_value(&mut config, "trimTrailingNewline", true, &mut diagnostics),
+    default_language: get_nullable_value(&mut config, "defaultLanguage", &mut diagnostics),
+    languages: get_language_map(&mut config, "languages", &mut diagnostics, |value| value.as_bool()),
+    language_extensions: get_language_map(
+      &mut config,
+      "languageExtensions",
+      &mut diagnostics,
+      |value| match value {
+        ConfigKeyValue::String(ext) => {
+          let ext = ext.trim_start_matches('.');
+          Some(if ext.is_empty() { None } else { Some(ext.to_string()) })
+        }
+        ConfigKeyValue::Null => Some(None),
+        _ => None,
+      },
+    ),
+    normalize_notebook: get_value(&mut config, "normalizeNotebook", false, &mut diagnostics),
+    strip_outputs: get_value(&mut config, "stripOutputs", false, &mut diagnostics),
+    strip_execution_counts: get_value(&mut config, "stripExecutionCounts", false, &mut diagnostics),
+    strip_transient_metadata: get_value(&mut config, "stripTransientMetadata", false, &mut diagnostics),
+    ignore_tag: get_value(&mut config, "ignoreTag", "dprint-ignore".to_string(), &mut diagnostics),
+    on_cell_error: get_value(&mut config, "onCellError", CellErrorBehavior::Skip, &mut diagnostics),
+    overrides: get_language_map(&mut config, "overrides", &mut diagnostics, |value| match value {
+      ConfigKeyValue::Object(override_config) => Some(override_config.clone()),
+      _ => None,
+    }),
+    jupytext_extensions: get_extensions(&mut config, "jupytextExtensions", &mut diagnostics),
+    databricks_extensions: get_extensions(&mut config, "databricksExtensions", &mut diagnostics),
+    upgrade_notebook: get_value(&mut config, "upgradeNotebook", false, &mut diagnostics),
+    fix_cell_ids: get_value(&mut config, "fixCellIds", false, &mut diagnostics),
+    output_conflicts: get_value(
+      &mut config,
+      "outputConflicts",
+      OutputConflictResolution::Error,
+      &mut diagnostics,
+    ),
+    new_line_kind: get_value(
+      &mut config,
+      "newLineKind",
+      global_config.new_line_kind.unwrap_or(NewLineKind::Auto),
+      &mut diagnostics,
+    ),
+    // jupyter indents notebooks with a single space
+    indent_width: get_value(
+      &mut config,
+      "indentWidth",
+      global_config.indent_width.unwrap_or(1),
+      &mut diagnostics,
+    ),
+    use_tabs: get_value(
+      &mut config,
+      "useTabs",
+      global_config.use_tabs.unwrap_or(false),
+      &mut diagnostics,
+    ),
+    source_array_layout: get_value(
+      &mut config,
+      "sourceArrayLayout",
+      SourceArrayLayout::Maintain,
+      &mut diagnostics,
+    ),
+    source_style: get_value(
+      &mut config,
+      "sourceStyle",
+      MultilineStringStyle::Preserve,
+      &mut diagnostics,
+    ),
+    output_text_style: get_value(
+      &mut config,
+      "outputTextStyle",
+      MultilineStringStyle::Preserve,
+      &mut diagnostics,
+    ),
+    validate_schema: get_value(&mut config, "validateSchema", false, &mut diagnostics),
   };
 
   diagnostics.extend(get_unknown_property_diagnostics(config));
@@ -39,3 +123,89 @@ pub fn resolve_config(
     diagnostics,
   }
 }
+
+/// Takes an array of file extensions from the configuration, trimming any leading period.
+fn get_extensions(config: &mut ConfigKeyMap, key: &str, diagnostics: &mut Vec<ConfigurationDiagnostic>) -> Vec<String> {
+  get_nullable_vec(
+    config,
+    key,
+    |value, index, diagnostics| match value {
+      ConfigKeyValue::String(ext) => Some(ext.trim_start_matches('.').to_string()),
+      _ => {
+        diagnostics.push(ConfigurationDiagnostic {
+          property_name: format!("{}[{}]", key, index),
+          message: "Expected a string.".to_string(),
+        });
+        None
+      }
+    },
+    diagnostics,
+  )
+  .unwrap_or_default()
+}
+
+/// Takes an object keyed by language from the configuration, lowercasing the keys.
+fn get_language_map<T>(
+  config: &mut ConfigKeyMap,
+  key: &str,
+  diagnostics: &mut Vec<ConfigurationDiagnostic>,
+  get_value: impl Fn(&ConfigKeyValue) -> Option<T>,
+) -> BTreeMap<String, T> {
+  let mut result = BTreeMap::new();
+  match config.shift_remove(key) {
+    Some(ConfigKeyValue::Object(values)) => {
+      for (language, value) in values {
+        match get_value(&value) {
+          Some(value) => {
+            result.insert(language.to_lowercase(), value);
+          }
+          None => diagnostics.push(ConfigurationDiagnostic {
+            property_name: format!("{}.{}", key, language),
+            message: "Unexpected value type.".to_string(),
+          }),
+        }
+      }
+    }
+    Some(ConfigKeyValue::Null) | None => {}
+    Some(_) => diagnostics.push(ConfigurationDiagnostic {
+      property_name: key.to_string(),
+      message: "Expected an object.".to_string(),
+    }),
+  }
+  result
+}
+
+/// Resolves the default configuration for tests.
+#[cfg(test)]
+pub fn default_config() -> Configuration {
+  resolve_config(ConfigKeyMap::new(), &Default::default()).config
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn handle_language_map_diagnostics() {
+    let mut config = ConfigKeyMap::new();
+    config.insert(
+      "languages".to_string(),
+      ConfigKeyValue::Object(ConfigKeyMap::from([
+        ("SQL".to_string(), ConfigKeyValue::Bool(false)),
+        ("python".to_string(), ConfigKeyValue::String("yes".to_string())),
+      ])),
+    );
+    config.insert("formatRawCells".to_string(), ConfigKeyValue::Number(1));
+    let result = resolve_config(config, &GlobalConfiguration::default());
+    assert_eq!(result.config.languages, BTreeMap::from([("sql".to_string(), false)]));
+    assert!(result.config.format_raw_cells);
+    assert_eq!(
+      result
+        .diagnostics
+        .iter()
+        .map(|d| d.property_name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["formatRawCells", "languages.python"]
+    );
+  }
+}
@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use crate::configuration::Configuration;
+use crate::languages::file_extension_to_language;
+use crate::text_cells::TextCell;
+use crate::text_cells::get_line_comment;
+use crate::text_cells::get_lines;
+use crate::text_cells::get_lines_range;
+
+/// Gets the language of the file when its extension is in
+/// the `databricksExtensions` configuration.
+pub fn get_databricks_language(file_path: &Path, config: &Configuration) -> Option<&'static str> {
+  let ext = file_path.extension()?.to_str()?.to_lowercase();
+  if !config
+    .databricks_extensions
+    .iter()
+    .any(|databricks_ext| databricks_ext.to_lowercase() == ext)
+  {
+    return None;
+  }
+  Some(file_extension_to_language(&ext).unwrap_or("python"))
+}
+
+/// Gets the cells of a notebook exported from Databricks, which starts with a
+/// `# Databricks notebook source` header and separates cells with
+/// `# COMMAND ----------` comments. Cells using a magic command (ex. `%md`)
+/// have each line prefixed with `# MAGIC`.
+///
+/// Returns `None` when the text doesn't have the header.
+pub fn parse_databricks_notebook<'a>(
+  text: &'a str,
+  language: &'a str,
+  config: &Configuration,
+) -> Option<Vec<TextCell<'a>>> {
+  let comment = get_line_comment(language);
+  let first_line = text.lines().next()?;
+  if first_line.trim_end() != format!("{} Databricks notebook source", comment) {
+    return None;
+  }
+  let separator = format!("{} COMMAND ----------", comment);
+  let magic_prefix = format!("{} MAGIC ", comment);
+
+  let mut cells = Vec::new();
+  let lines = get_lines(text);
+  let mut index = 0;
+  let mut cell_start = 1;
+  for i in 1..=lines.len() {
+    if lines.get(i).is_some_and(|(_, line)| line.trim_end() != separator) {
+      continue;
+    }
+    let cell_lines = &lines[cell_start..i];
+    cell_start = i + 1;
+    // skip the blank lines and title around the cell's text
+    let Some(first) = cell_lines
+      .iter()
+      .position(|(_, line)| !line.trim().is_empty() && !line.starts_with(&format!("{} DBTITLE", comment)))
+    else {
+      index += 1;
+      continue;
+    };
+    let last = cell_lines
+      .iter()
+      .rposition(|(_, line)| !line.trim().is_empty())
+      .unwrap();
+    let cell_lines = &cell_lines[first..=last];
+    let cell_index = index;
+    index += 1;
+
+    let is_magic_line = |line: &str| line.starts_with(&magic_prefix) || line.trim_end() == magic_prefix.trim_end();
+    let (_, first_line) = cell_lines[0];
+    if !is_magic_line(first_line) {
+      if config.format_code_cells && !cell_lines.iter().any(|(_, line)| is_magic_line(line)) {
+        cells.push(TextCell {
+          index: cell_index,
+          language,
+          range: get_lines_range(cell_lines),
+          line_prefix: None,
+        });
+      }
+      continue;
+    }
+
+    // the first line has the magic command and the text is on the following lines
+    let magic_command = first_line[magic_prefix.len().min(first_line.len())..].trim();
+    let Some(cell_language) = magic_command_to_language(magic_command) else {
+      continue;
+    };
+    let is_enabled = if cell_language == "markdown" {
+      config.format_markdown_cells
+    } else {
+      config.format_code_cells
+    };
+    if is_enabled && cell_lines.len() > 1 {
+      cells.push(TextCell {
+        index: cell_index,
+        language: cell_language,
+        range: get_lines_range(&cell_lines[1..]),
+        line_prefix: Some(magic_prefix.clone()),
+      });
+    }
+  }
+  Some(cells)
+}
+
+fn magic_command_to_language(magic_command: &str) -> Option<&'static str> {
+  let language = match magic_command {
+    "%md" | "%md-sandbox" => "markdown",
+    "%python" => "python",
+    "%r" => "r",
+    "%scala" => "scala",
+    "%sh" => "bash",
+    "%sql" => "sql",
+    _ => return None,
+  };
+  Some(language)
+}
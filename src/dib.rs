@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::configuration::Configuration;
+use crate::languages::kernel_name_to_language;
+use crate::text_cells::TextCell;
+use crate::text_cells::get_lines;
+use crate::text_cells::get_lines_range;
+
+/// Kernels of Polyglot Notebooks that aren't formatted.
+const OTHER_KERNELS: &[&str] = &["kql", "mermaid", "meta", "value"];
+
+pub fn is_dib_notebook(file_path: &Path) -> bool {
+  file_path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .is_some_and(|ext| ext.eq_ignore_ascii_case("dib"))
+}
+
+/// Gets the cells of a Polyglot Notebook stored in the `.dib` format,
+/// which starts each cell with a kernel line (ex. `#!csharp`).
+///
+/// Directives at the start of a cell (ex. `#!time` or `#r "nuget: ..."`)
+/// are not included in the cell.
+pub fn parse_dib_notebook<'a>(text: &'a str, config: &Configuration) -> Vec<TextCell<'a>> {
+  let lines = get_lines(text);
+  let kernel_languages = get_meta_kernel_languages(text);
+  let get_kernel_language = |line: &str| -> Option<&'a str> {
+    let name = line.trim_end().strip_prefix("#!")?;
+    get_kernel_language_by_name(name).or_else(|| kernel_languages.get(name).copied())
+  };
+
+  let mut cells = Vec::new();
+  let mut index = 0;
+  let mut kernel_line = None;
+  let mut content_start = 0;
+  for i in 0..=lines.len() {
+    let next_kernel_line = lines.get(i).filter(|(_, line)| get_kernel_language(line).is_some());
+    if i < lines.len() && next_kernel_line.is_none() {
+      continue;
+    }
+    // the #!meta cell has the notebook's metadata
+    if let Some((_, kernel_line)) = kernel_line
+      && let Some(language) = get_kernel_language(kernel_line).filter(|language| *language != "meta")
+    {
+      let cell_index = index;
+      index += 1;
+      let is_enabled = if language == "markdown" {
+        config.format_markdown_cells
+      } else {
+        config.format_code_cells
+      };
+      let content_lines = &lines[content_start..i];
+      let first = content_lines
+        .iter()
+        .position(|(_, line)| !line.trim().is_empty() && !is_directive_line(line));
+      let last = content_lines.iter().rposition(|(_, line)| !line.trim().is_empty());
+      if is_enabled && let (Some(first), Some(last)) = (first, last) {
+        cells.push(TextCell {
+          index: cell_index,
+          language,
+          range: get_lines_range(&content_lines[first..=last]),
+          line_prefix: None,
+        });
+      }
+    }
+    kernel_line = next_kernel_line.copied();
+    content_start = i + 1;
+  }
+  cells
+}
+
+/// Gets the language of a built-in kernel or one of its aliases. Kernels
+/// without a language to format them with are returned as-is (ex. `kql`).
+fn get_kernel_language_by_name(name: &str) -> Option<&'static str> {
+  kernel_name_to_language(name).or_else(|| {
+    OTHER_KERNELS
+      .iter()
+      .find(|kernel| kernel.eq_ignore_ascii_case(name))
+      .copied()
+  })
+}
+
+/// Gets the languages of the kernels declared in the `#!meta` cell.
+fn get_meta_kernel_languages(text: &str) -> HashMap<String, &'static str> {
+  let mut languages = HashMap::new();
+  let Some(meta_start) = text.find("#!meta") else {
+    return languages;
+  };
+  let Some(json_text) = text[meta_start..].lines().skip(1).find(|line| !line.trim().is_empty()) else {
+    return languages;
+  };
+  let Ok(meta) = serde_json::from_str::<serde_json::Value>(json_text) else {
+    return languages;
+  };
+  let items = meta
+    .pointer("/kernelInfo/items")
+    .and_then(|items| items.as_array())
+    .into_iter()
+    .flatten();
+  for item in items {
+    let Some(language) = item
+      .get("languageName")
+      .and_then(|name| name.as_str())
+      .and_then(get_kernel_language_by_name)
+    else {
+      continue;
+    };
+    let names = item.get("name").into_iter().chain(
+      item
+        .get("aliases")
+        .and_then(|aliases| aliases.as_array())
+        .into_iter()
+        .flatten(),
+    );
+    for name in names.filter_map(|name| name.as_str()) {
+      languages.insert(name.to_string(), language);
+    }
+  }
+  languages
+}
+
+fn is_directive_line(line: &str) -> bool {
+  (line.starts_with("#!") && !line.starts_with("#!/")) || line.starts_with("#r ") || line.starts_with("#i ")
+}
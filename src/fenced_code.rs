@@ -0,0 +1,111 @@
+use std::ops::Range;
+
+/// A fenced code block in markdown (ex. a block starting with ```` ```python ````).
+pub struct FencedCodeBlock<'a> {
+  /// Text after the opening fence (ex. `python` or `{r setup}`).
+  pub info: &'a str,
+  /// Indentation of the opening fence.
+  pub indent: &'a str,
+  /// Range of the code, excluding the final newline.
+  pub range: Range<usize>,
+}
+
+/// Gets the closed fenced code blocks in the markdown text.
+pub fn parse_fenced_code_blocks(text: &str) -> Vec<FencedCodeBlock<'_>> {
+  struct OpenFence<'a> {
+    fence_char: char,
+    fence_len: usize,
+    indent: &'a str,
+    info: &'a str,
+    content_start: usize,
+  }
+
+  let mut blocks = Vec::new();
+  let mut open_fence: Option<OpenFence> = None;
+  let mut offset = 0;
+  for line in text.split_inclusive('\n') {
+    let line_start = offset;
+    offset += line.len();
+    let Some((indent, fence_char, fence_len, rest)) = parse_fence(line) else {
+      continue;
+    };
+    match &open_fence {
+      None => {
+        let info = rest.trim();
+        if fence_char == '`' && info.contains('`') {
+          continue;
+        }
+        open_fence = Some(OpenFence {
+          fence_char,
+          fence_len,
+          indent,
+          info,
+          content_start: offset,
+        });
+      }
+      Some(fence) if fence.fence_char == fence_char && fence_len >= fence.fence_len && rest.trim().is_empty() => {
+        let content_end = if line_start > fence.content_start {
+          let content = &text[fence.content_start..line_start];
+          fence.content_start + content.trim_end_matches('\n').trim_end_matches('\r').len()
+        } else {
+          fence.content_start
+        };
+        blocks.push(FencedCodeBlock {
+          info: fence.info,
+          indent: fence.indent,
+          range: fence.content_start..content_end,
+        });
+        open_fence = None;
+      }
+      Some(_) => {}
+    }
+  }
+  blocks
+}
+
+/// Parses a line that starts with a fence, returning the indentation,
+/// the fence character, the length of the fence and the text after it.
+fn parse_fence(line: &str) -> Option<(&str, char, usize, &str)> {
+  let text = line.trim_start_matches(' ');
+  let indent_len = line.len() - text.len();
+  if indent_len > 3 {
+    return None;
+  }
+  let fence_char = text.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+  let fence_len = text.len() - text.trim_start_matches(fence_char).len();
+  if fence_len < 3 {
+    return None;
+  }
+  Some((&line[..indent_len], fence_char, fence_len, &text[fence_len..]))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn get_blocks(text: &str) -> Vec<(&str, &str, &str)> {
+    parse_fenced_code_blocks(text)
+      .into_iter()
+      .map(|block| (block.info, block.indent, &text[block.range]))
+      .collect()
+  }
+
+  #[test]
+  fn parses_fenced_code_blocks() {
+    assert_eq!(
+      get_blocks("# Title\n\n```python\nx = 1\n\ny = 2\n```\n\n~~~~ {r}\n```\nz\n~~~~\n  ```\n  a\n  ```\n```\n```\n"),
+      vec![
+        ("python", "", "x = 1\n\ny = 2"),
+        ("{r}", "", "```\nz"),
+        ("", "  ", "  a"),
+        ("", "", ""),
+      ]
+    );
+    // unclosed
+    assert_eq!(get_blocks("```python\nx = 1\n"), vec![]);
+    // backticks in the info string
+    assert_eq!(get_blocks("``` `a` ```\n"), vec![]);
+    // crlf
+    assert_eq!(get_blocks("```ts\r\nlet x;\r\n```\r\n"), vec![("ts", "", "let x;")]);
+  }
+}
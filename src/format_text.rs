@@ -1,21 +1,90 @@
-use std::borrow::Cow;
+use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
 
+use crate::canonical_json::to_canonical_json;
+use crate::cell_error::CellFormatError;
+use crate::cell_error::combine_cell_errors;
+use crate::cell_error::find_error_position;
+use crate::cell_ids::get_cell_id_text_changes;
+use crate::configuration::CellErrorBehavior;
+use crate::configuration::Configuration;
+use crate::configuration::MultilineStringStyle;
+use crate::configuration::SourceArrayLayout;
+use crate::databricks::get_databricks_language;
+use crate::databricks::parse_databricks_notebook;
+use crate::dib::is_dib_notebook;
+use crate::dib::parse_dib_notebook;
+use crate::ipython_magics::CellMagicBodyKind;
+use crate::ipython_magics::mask_ipython_syntax;
+use crate::ipython_magics::parse_cell_magic;
+use crate::jupytext::get_percent_script_language;
+use crate::jupytext::parse_percent_script;
+use crate::languages::file_extension_to_language;
+use crate::languages::kernel_name_to_language;
+use crate::languages::mime_type_to_language;
+use crate::languages::resolve_language_extension;
+use crate::markdown_code_blocks::parse_markdown_code_blocks;
+use crate::merge_conflicts::has_merge_conflict_markers;
+use crate::merge_conflicts::resolve_merge_conflicts;
+use crate::multiline_strings::build_array_json_text;
+use crate::multiline_strings::build_expanded_array_json_text;
+use crate::multiline_strings::get_output_text_changes;
+use crate::quarto::is_quarto_document;
+use crate::quarto::parse_quarto_document;
+use crate::source_map::LineAndColumn;
+use crate::source_map::get_notebook_position;
+use crate::source_map::get_source_offset;
+use crate::strip_outputs::get_strip_text_changes;
+use crate::text_cells::TextCell;
+use crate::text_cells::format_text_cell;
+use crate::text_cells::format_text_cells;
 use crate::text_changes::TextChange;
 use crate::text_changes::apply_text_changes;
+use crate::upgrade::upgrade_v3_notebook;
+use crate::validate_schema::combine_schema_violations;
+use crate::validate_schema::validate_notebook_schema;
 use anyhow::Result;
+use dprint_core::configuration::ConfigKeyMap;
+use dprint_core::configuration::resolve_new_line_kind;
 use jsonc_parser::CollectOptions;
 use jsonc_parser::CommentCollectionStrategy;
 use jsonc_parser::ParseOptions;
 
+/// A request to format the text of a cell with another plugin.
+pub struct HostFormatRequest<'a> {
+  pub file_path: &'a Path,
+  pub file_text: String,
+  /// Byte range within the text to format or `None` to format all of it.
+  pub range: Option<Range<usize>>,
+  /// Configuration to use instead of the plugin's configuration.
+  pub override_config: Option<&'a ConfigKeyMap>,
+}
+
+/// Formats the cells of a notebook, a Polyglot `.dib` notebook, a Jupytext percent
+/// script, a Databricks notebook export or the code chunks of a Quarto or R Markdown
+/// document.
+///
+/// When a byte range is provided, only the cells whose source intersects
+/// the range are formatted.
 pub fn format_text(
+  file_path: &Path,
   input_text: &str,
-  format_with_host: impl FnMut(&Path, String) -> Result<Option<String>>,
+  range: Option<Range<usize>>,
+  config: &Configuration,
+  format_with_host: impl FnMut(HostFormatRequest) -> Result<Option<String>>,
 ) -> Result<Option<String>> {
   let had_bom = input_text.starts_with("\u{FEFF}");
-  let input_text = if had_bom { &input_text[3..] } else { input_text };
-  let result = format_inner(input_text, format_with_host)?;
+  let (input_text, range) = if had_bom {
+    let range = range.map(|range| range.start.saturating_sub(3)..range.end.saturating_sub(3));
+    (&input_text[3..], range)
+  } else {
+    (input_text, range)
+  };
+  let result = match get_text_notebook_cells(file_path, input_text, config) {
+    Some(cells) => format_text_cells(input_text, &cells, range.as_ref(), config, format_with_host)?,
+    None => format_inner(input_text, range, config, format_with_host)?,
+  };
   if result.is_none() && had_bom {
     Ok(Some(input_text.to_string()))
   } else {
@@ -23,12 +92,141 @@ pub fn format_text(
   }
 }
 
+/// Gets the cells of a notebook stored as a text file or `None` for a Jupyter notebook.
+fn get_text_notebook_cells<'a>(file_path: &Path, text: &'a str, config: &Configuration) -> Option<Vec<TextCell<'a>>> {
+  if let Some(language) = get_databricks_language(file_path, config)
+    && let Some(cells) = parse_databricks_notebook(text, language, config)
+  {
+    Some(cells)
+  } else if let Some(language) = get_percent_script_language(file_path, config) {
+    Some(parse_percent_script(text, language, config))
+  } else if is_quarto_document(file_path) {
+    Some(parse_quarto_document(text, config))
+  } else if is_dib_notebook(file_path) {
+    Some(parse_dib_notebook(text, config))
+  } else if get_databricks_language(file_path, config).is_some() {
+    // not a databricks notebook, so leave it as-is
+    Some(Vec::new())
+  } else {
+    None
+  }
+}
+
 fn format_inner(
   input_text: &str,
-  format_with_host: impl FnMut(&Path, String) -> Result<Option<String>>,
+  range: Option<Range<usize>>,
+  config: &Configuration,
+  format_with_host: impl FnMut(HostFormatRequest) -> Result<Option<String>>,
 ) -> Result<Option<String>> {
-  let parse_result = jsonc_parser::parse_to_ast(
-    input_text,
+  if has_merge_conflict_markers(input_text) {
+    // the range is within the conflicted text, so it doesn't apply to the resolved notebook
+    if range.is_some() {
+      anyhow::bail!("Formatting a range of a notebook with merge conflicts is not supported.");
+    }
+    let resolved_text = resolve_merge_conflicts(input_text, config.output_conflicts)?;
+    let result = format_inner(&resolved_text, None, config, format_with_host)?;
+    return Ok(Some(result.unwrap_or(resolved_text)));
+  }
+  let parse_result = parse_notebook(input_text)?;
+  let Some(root_value) = parse_result.value else {
+    return Ok(None);
+  };
+  if root_value.as_object().is_some_and(has_ignore_metadata) {
+    return Ok(None);
+  }
+  if config.validate_schema {
+    let mut violations = validate_notebook_schema(input_text, &root_value);
+    // the ids are fixed after formatting
+    if config.fix_cell_ids && range.is_none() {
+      violations.retain(|violation| !violation.is_cell_id_violation());
+    }
+    if !violations.is_empty() {
+      return Err(combine_schema_violations(
+        "The notebook doesn't match the nbformat schema:",
+        violations,
+      ));
+    }
+  }
+
+  let result = format_root(input_text, &root_value, range.as_ref(), config, format_with_host)?;
+  // only the selected cells are changed when formatting a range
+  let result = if config.upgrade_notebook && range.is_none() {
+    let text = result.as_deref().unwrap_or(input_text);
+    match parse_notebook(text)?.value {
+      Some(value) => match upgrade_v3_notebook(&to_canonical_json(&value)) {
+        Some(upgraded_text) => Some(upgraded_text),
+        None => result,
+      },
+      None => result,
+    }
+  } else {
+    result
+  };
+  let result = if config.fix_cell_ids && range.is_none() {
+    let text = result.as_deref().unwrap_or(input_text);
+    let text_changes = match parse_notebook(text)?.value {
+      Some(value) => get_cell_id_text_changes(text, &value),
+      None => Vec::new(),
+    };
+    if text_changes.is_empty() {
+      result
+    } else {
+      Some(apply_text_changes(text, text_changes))
+    }
+  } else {
+    result
+  };
+  let result = if config.normalize_notebook && range.is_none() {
+    let text = result.as_deref().unwrap_or(input_text);
+    match parse_notebook(text)?.value {
+      Some(value) => {
+        let normalized_text = to_canonical_json(&value);
+        if normalized_text == input_text {
+          None
+        } else {
+          Some(normalized_text)
+        }
+      }
+      None => result,
+    }
+  } else {
+    result
+  };
+  let result = if range.is_none() {
+    let text = result.as_deref().unwrap_or(input_text);
+    match convert_new_lines(text, resolve_new_line_kind(input_text, config.new_line_kind)) {
+      Some(converted_text) if converted_text == input_text => None,
+      Some(converted_text) => Some(converted_text),
+      None => result,
+    }
+  } else {
+    result
+  };
+
+  Ok(match result {
+    Some(text) => {
+      #[cfg(debug_assertions)]
+      validate_output_json(&text)?;
+      if config.validate_schema
+        && let Some(value) = parse_notebook(&text)?.value
+      {
+        let violations = validate_notebook_schema(&text, &value);
+        if !violations.is_empty() {
+          return Err(combine_schema_violations(
+            "Formatting produced a notebook that doesn't match the nbformat schema:",
+            violations,
+          ));
+        }
+      }
+      Some(text)
+    }
+    None => None,
+  })
+}
+
+pub fn parse_notebook(text: &str) -> Result<jsonc_parser::ParseResult<'_>> {
+  Ok(jsonc_parser::parse_to_ast(
+    text,
     &CollectOptions {
       comments: CommentCollectionStrategy::Off,
       tokens: false,
@@ -42,43 +240,80 @@ fn format_inner(
       allow_hexadecimal_numbers: true,
       allow_unary_plus_numbers: true,
     },
-  )?;
-  let Some(root_value) = parse_result.value else {
-    return Ok(None);
-  };
-
-  Ok(match format_root(input_text, &root_value, format_with_host) {
-    Some(text) => {
-      #[cfg(debug_assertions)]
-      validate_output_json(&text)?;
-      Some(text)
-    }
-    None => None,
-  })
+  )?)
 }
 
 fn format_root(
   input_text: &str,
   root_value: &jsonc_parser::ast::Value,
-  mut format_with_host: impl FnMut(&Path, String) -> Result<Option<String>>,
-) -> Option<String> {
-  let root_obj = root_value.as_object()?;
-  let maybe_default_language = get_metadata_language(root_obj);
-  let cells = root_value.as_object()?.get_array("cells")?;
+  range: Option<&Range<usize>>,
+  config: &Configuration,
+  mut format_with_host: impl FnMut(HostFormatRequest) -> Result<Option<String>>,
+) -> Result<Option<String>> {
+  let Some(root_obj) = root_value.as_object() else {
+    return Ok(None);
+  };
+  let maybe_default_language = get_metadata_language(root_obj).or(config.default_language.as_deref());
+  let new_line = resolve_new_line_kind(input_text, config.new_line_kind);
+  let cells = get_cells(root_obj);
 
-  let text_changes: Vec<TextChange> = cells
-    .elements
-    .iter()
-    .filter_map(|element| get_cell_text_change(input_text, element, maybe_default_language, &mut format_with_host))
-    .collect();
+  let mut text_changes = Vec::new();
+  let mut cell_errors = Vec::new();
+  for (index, element) in cells.into_iter().enumerate() {
+    let Some(cell) = element.as_object() else {
+      continue;
+    };
+    if range.is_some_and(|range| !ranges_intersect(range, &(cell.range.start..cell.range.end))) {
+      continue;
+    }
+    let cell_info = CellInfo {
+      index,
+      maybe_default_language,
+      new_line,
+    };
+    let source_text_change =
+      match get_cell_text_change(input_text, cell, &cell_info, range, config, &mut format_with_host) {
+        Ok(text_change) => text_change,
+        Err(err) => {
+          cell_errors.push(err);
+          None
+        }
+      };
+    text_changes
+      .extend(source_text_change.or_else(|| get_source_style_text_change(input_text, cell, config, new_line)));
+    if !is_cell_ignored(cell, config) {
+      text_changes.extend(get_strip_text_changes(cell, config));
+      text_changes.extend(get_output_text_changes(input_text, cell, config, new_line));
+    }
+  }
+
+  if !cell_errors.is_empty() && config.on_cell_error == CellErrorBehavior::Error {
+    return Err(combine_cell_errors(cell_errors));
+  }
 
   if text_changes.is_empty() {
-    None
+    Ok(None)
   } else {
-    Some(apply_text_changes(input_text, text_changes))
+    Ok(Some(apply_text_changes(input_text, text_changes)))
   }
 }
 
+/// Gets the cells of the notebook, which are within `worksheets` before nbformat 4.
+fn get_cells<'a>(root_obj: &'a jsonc_parser::ast::Object<'a>) -> Vec<&'a jsonc_parser::ast::Value<'a>> {
+  if let Some(cells) = root_obj.get_array("cells") {
+    return cells.elements.iter().collect();
+  }
+  let Some(worksheets) = root_obj.get_array("worksheets") else {
+    return Vec::new();
+  };
+  worksheets
+    .elements
+    .iter()
+    .filter_map(|worksheet| worksheet.as_object()?.get_array("cells"))
+    .flat_map(|cells| cells.elements.iter())
+    .collect()
+}
+
 #[cfg(debug_assertions)]
 fn validate_output_json(text: &str) -> Result<()> {
   // ensures the output is correct in debug mode
@@ -111,55 +346,370 @@ fn validate_output_json(text: &str) -> Result<()> {
   }
 }
 
+struct CellInfo<'a> {
+  index: usize,
+  maybe_default_language: Option<&'a str>,
+  /// Newline of the notebook's text, which is used between the lines of a source array.
+  new_line: &'static str,
+}
+
+/// Gets the text change for formatting the cell's source.
+///
+/// Returns an error when the plugin failed formatting the cell.
 fn get_cell_text_change(
   file_text: &str,
-  cell: &jsonc_parser::ast::Value,
-  maybe_default_language: Option<&str>,
-  format_with_host: &mut impl FnMut(&Path, String) -> Result<Option<String>>,
-) -> Option<TextChange> {
-  let cell = cell.as_object()?;
-  let cell_language = get_cell_vscode_language_id(cell).or_else(|| {
-    let cell_type = cell.get_string("cell_type")?;
-    match cell_type.value.as_ref() {
-      "markdown" => Some("markdown"),
-      "code" => maybe_default_language,
-      _ => None,
+  cell: &jsonc_parser::ast::Object,
+  cell_info: &CellInfo,
+  range: Option<&Range<usize>>,
+  config: &Configuration,
+  format_with_host: &mut impl FnMut(HostFormatRequest) -> Result<Option<String>>,
+) -> Result<Option<TextChange>, CellFormatError> {
+  let cell_language = get_cell_language(cell, cell_info, config);
+  let format_code_blocks = config.format_markdown_code_blocks
+    && cell
+      .get_string("cell_type")
+      .is_some_and(|cell_type| cell_type.value == "markdown")
+    && !is_cell_ignored(cell, config);
+  if cell_language.is_none() && !format_code_blocks {
+    return Ok(None);
+  }
+  let Some(code_block) = analyze_code_block(cell, file_text) else {
+    return Ok(None);
+  };
+  if has_ignore_comment(&code_block.source) {
+    return Ok(None);
+  }
+  let source_range = match range {
+    Some(range) if !ranges_intersect(range, &code_block.replace_range) => return Ok(None),
+    // forward the range to the plugin when the selection is within the cell
+    Some(range) if code_block.replace_range.start <= range.start && range.end <= code_block.replace_range.end => {
+      let start = get_source_offset(file_text, &code_block.literal_ranges, range.start);
+      let end = get_source_offset(file_text, &code_block.literal_ranges, range.end);
+      Some(start..end)
     }
-  })?;
+    _ => None,
+  };
+  // positions in errors for a cell magic's body are relative to the body
+  let body_line_offset = parse_cell_magic(&code_block.source)
+    .map(|cell_magic| cell_magic.header.matches('\n').count())
+    .unwrap_or(0);
+  // keep the newlines within the source the same as before formatting
+  let source_new_line = if code_block.source.contains("\r\n") {
+    "\r\n"
+  } else {
+    "\n"
+  };
+  let formatted_text = match cell_language {
+    Some(cell_language) => match format_code_block_text(
+      cell_language,
+      code_block.source.clone(),
+      source_range,
+      config,
+      format_with_host,
+    ) {
+      Ok(formatted_text) => formatted_text,
+      Err(error) => {
+        let position = find_error_position(&format!("{:#}", error)).and_then(|position| {
+          let source_position = LineAndColumn {
+            line: position.line + body_line_offset,
+            column: position.column,
+          };
+          get_notebook_position(file_text, &code_block.literal_ranges, source_position)
+        });
+        return Err(CellFormatError {
+          index: cell_info.index,
+          id: cell.get_string("id").map(|id| id.value.to_string()),
+          language: cell_language.to_string(),
+          position,
+          error,
+        });
+      }
+    },
+    None => None,
+  };
+  let formatted_text = formatted_text.map(|formatted_text| {
+    let formatted_text = convert_new_lines(&formatted_text, source_new_line).unwrap_or(formatted_text);
+    if config.trim_trailing_newline {
+      // many plugins will add a final newline, but that doesn't look nice in notebooks, so trim it off
+      formatted_text.trim_end().to_string()
+    } else {
+      formatted_text
+    }
+  });
+  let formatted_text = if format_code_blocks {
+    let markdown_text = formatted_text.as_deref().unwrap_or(&code_block.source);
+    let mut text_changes = Vec::new();
+    for code_block_cell in parse_markdown_code_blocks(markdown_text, config) {
+      // like the markdown plugin, leave code blocks that fail to format as-is
+      if let Ok(Some(new_text)) = format_text_cell(markdown_text, &code_block_cell, None, config, format_with_host) {
+        text_changes.push(TextChange {
+          range: code_block_cell.range,
+          new_text,
+        });
+      }
+    }
+    if text_changes.is_empty() {
+      formatted_text
+    } else {
+      Some(apply_text_changes(markdown_text, text_changes))
+    }
+  } else {
+    formatted_text
+  };
+  let Some(formatted_text) = formatted_text else {
+    return Ok(None);
+  };
+
+  Ok(Some(get_source_text_change(
+    file_text,
+    &code_block,
+    &formatted_text,
+    config,
+    cell_info.new_line,
+  )))
+}
+
+/// Gets the text change that writes the text as the cell's source, converting
+/// between a string and an array of lines based on the `sourceStyle`.
+fn get_source_text_change(
+  file_text: &str,
+  code_block: &CodeBlockText,
+  text: &str,
+  config: &Configuration,
+  new_line: &str,
+) -> TextChange {
+  let write_array = match config.source_style {
+    MultilineStringStyle::Preserve => code_block.array_range.is_some(),
+    MultilineStringStyle::Array => true,
+    MultilineStringStyle::String => false,
+  };
+  match (&code_block.array_range, write_array) {
+    (None, false) => TextChange {
+      range: code_block.replace_range.clone(),
+      new_text: serde_json::to_string(text).unwrap(),
+    },
+    (Some(array_range), false) => TextChange {
+      range: array_range.clone(),
+      new_text: serde_json::to_string(text).unwrap(),
+    },
+    (None, true) => TextChange {
+      range: code_block.replace_range.clone(),
+      new_text: build_expanded_array_json_text(file_text, code_block.replace_range.start, text, config, new_line),
+    },
+    (Some(array_range), true) if text.is_empty() => TextChange {
+      range: array_range.clone(),
+      new_text: "[]".to_string(),
+    },
+    (Some(array_range), true)
+      if file_text[array_range.clone()].contains('\n') && !code_block.literal_ranges.is_empty() =>
+    {
+      TextChange {
+        range: code_block.replace_range.clone(),
+        new_text: build_array_json_text(text, &format!(",{}{}", new_line, code_block.indent_text)),
+      }
+    }
+    (Some(_), true) if config.source_array_layout == SourceArrayLayout::Maintain => TextChange {
+      range: code_block.replace_range.clone(),
+      new_text: build_array_json_text(text, ", "),
+    },
+    (Some(array_range), true) => TextChange {
+      range: array_range.clone(),
+      new_text: build_expanded_array_json_text(file_text, array_range.start, text, config, new_line),
+    },
+  }
+}
+
+/// Gets the text change that converts the source of a cell that wasn't
+/// formatted to the `sourceStyle` and `sourceArrayLayout`.
+fn get_source_style_text_change(
+  file_text: &str,
+  cell: &jsonc_parser::ast::Object,
+  config: &Configuration,
+  new_line: &str,
+) -> Option<TextChange> {
+  if config.source_style == MultilineStringStyle::Preserve && config.source_array_layout == SourceArrayLayout::Maintain
+  {
+    return None;
+  }
+  if is_cell_ignored(cell, config) {
+    return None;
+  }
   let code_block = analyze_code_block(cell, file_text)?;
-  let file_path = language_to_path(cell_language)?;
-  let formatted_text = format_with_host(&file_path, code_block.source).ok()??;
-  // many plugins will add a final newline, but that doesn't look nice in notebooks, so trim it off
-  let formatted_text = formatted_text.trim_end();
+  if has_ignore_comment(&code_block.source) {
+    return None;
+  }
+  let write_array = match config.source_style {
+    MultilineStringStyle::Preserve => code_block.array_range.is_some(),
+    MultilineStringStyle::Array => true,
+    MultilineStringStyle::String => false,
+  };
+  let expands_array = write_array
+    && config.source_array_layout == SourceArrayLayout::Expand
+    && !code_block.source.is_empty()
+    && code_block
+      .array_range
+      .as_ref()
+      .is_some_and(|array_range| !file_text[array_range.clone()].contains('\n'));
+  if code_block.array_range.is_some() == write_array && !expands_array {
+    return None;
+  }
+  Some(get_source_text_change(
+    file_text,
+    &code_block,
+    &code_block.source,
+    config,
+    new_line,
+  ))
+}
 
-  let new_text = if code_block.is_array {
-    build_array_json_text(formatted_text, code_block.indent_text)
-  } else {
-    serde_json::to_string(&formatted_text).unwrap()
+/// Gets the language to format the cell with or `None` when it shouldn't be formatted.
+fn get_cell_language<'a>(
+  cell: &'a jsonc_parser::ast::Object<'a>,
+  cell_info: &CellInfo<'a>,
+  config: &Configuration,
+) -> Option<&'a str> {
+  let cell_type = cell.get_string("cell_type").map(|cell_type| cell_type.value.as_ref());
+  let is_cell_type_enabled = match cell_type {
+    Some("code") => config.format_code_cells,
+    Some("markdown") => config.format_markdown_cells,
+    Some("raw") => config.format_raw_cells,
+    _ => true,
+  };
+  if !is_cell_type_enabled {
+    return None;
+  }
+  let cell_language = get_cell_vscode_language_id(cell).or(match cell_type {
+    Some("markdown") => Some("markdown"),
+    // nbformat 3 stores the language on each code cell
+    Some("code") => cell
+      .get_string("language")
+      .map(|language| language.value.as_ref())
+      .filter(|language| !language.is_empty())
+      .or(cell_info.maybe_default_language),
+    Some("raw") => get_raw_cell_language(cell),
+    _ => None,
+  })?;
+  if is_cell_ignored(cell, config) {
+    return None;
+  }
+  Some(cell_language)
+}
+
+pub fn format_code_block_text(
+  language: &str,
+  text: String,
+  range: Option<Range<usize>>,
+  config: &Configuration,
+  format_with_host: &mut impl FnMut(HostFormatRequest) -> Result<Option<String>>,
+) -> Result<Option<String>> {
+  if !is_language_enabled(language, config) {
+    return Ok(None);
+  }
+  let Some(file_path) = language_to_path(language, config) else {
+    return Ok(None);
   };
+  let override_config = get_override_config(language, &file_path, config);
+  format_file_text(&file_path, text, range, override_config, config, format_with_host)
+}
 
-  Some(TextChange {
-    range: code_block.replace_range,
-    new_text,
+/// Gets the configuration to send to the plugin for the language, falling back to
+/// the language of the file extension it's formatted with (ex. `python` for `ipython3`).
+fn get_override_config<'a>(language: &str, file_path: &Path, config: &'a Configuration) -> Option<&'a ConfigKeyMap> {
+  config.overrides.get(&language.to_lowercase()).or_else(|| {
+    let ext = file_path.extension()?.to_str()?;
+    config.overrides.get(file_extension_to_language(ext)?)
   })
 }
 
+fn format_file_text(
+  file_path: &Path,
+  text: String,
+  range: Option<Range<usize>>,
+  override_config: Option<&ConfigKeyMap>,
+  config: &Configuration,
+  format_with_host: &mut impl FnMut(HostFormatRequest) -> Result<Option<String>>,
+) -> Result<Option<String>> {
+  if file_path.extension().and_then(|ext| ext.to_str()) == Some("py") {
+    if let Some(cell_magic) = parse_cell_magic(&text) {
+      let body = cell_magic.body.to_string();
+      let header_len = cell_magic.header.len();
+      let body_range = range.map(|range| range.start.saturating_sub(header_len)..range.end.saturating_sub(header_len));
+      let maybe_formatted_body = match cell_magic.body_kind {
+        CellMagicBodyKind::Language(language) => {
+          format_code_block_text(language, body, body_range, config, format_with_host)?
+        }
+        CellMagicBodyKind::FileExtension(ext) => {
+          let language = file_extension_to_language(ext);
+          if language.is_some_and(|language| !is_language_enabled(language, config)) {
+            return Ok(None);
+          }
+          format_file_text(
+            &PathBuf::from(format!("code_block.{}", ext)),
+            body,
+            body_range,
+            language.and_then(|language| config.overrides.get(language)),
+            config,
+            format_with_host,
+          )?
+        }
+      };
+      return Ok(maybe_formatted_body.map(|formatted_body| format!("{}{}", cell_magic.header, formatted_body)));
+    }
+
+    // ipython syntax would cause the python formatter to error
+    let Some(masked_source) = mask_ipython_syntax(&text) else {
+      return Ok(None);
+    };
+    // the masked lines shift the offsets, so format the whole cell in that case
+    let range = if masked_source.text == text { range } else { None };
+    let maybe_formatted_text = format_with_host(HostFormatRequest {
+      file_path,
+      file_text: masked_source.text.clone(),
+      range,
+      override_config,
+    })?;
+    Ok(maybe_formatted_text.and_then(|formatted_text| masked_source.unmask(&formatted_text)))
+  } else {
+    format_with_host(HostFormatRequest {
+      file_path,
+      file_text: text,
+      range,
+      override_config,
+    })
+  }
+}
+
+/// Gets if the ranges overlap or touch.
+pub fn ranges_intersect(a: &Range<usize>, b: &Range<usize>) -> bool {
+  a.start <= b.end && b.start <= a.end
+}
+
+fn is_language_enabled(language: &str, config: &Configuration) -> bool {
+  config.languages.get(&language.to_lowercase()).copied().unwrap_or(true)
+}
+
 struct CodeBlockText<'a> {
   // Can be either a string or an array of strings.
   // (https://github.com/jupyter/nbformat/blob/0708dd627d9ef81b12f231defb0d94dd7e80e3f4/nbformat/v4/nbformat.v4.5.schema.json#L460C7-L468C8)
-  is_array: bool,
+  /// Range of the array, including the brackets, when the source is an array.
+  array_range: Option<Range<usize>>,
   indent_text: &'a str,
-  replace_range: std::ops::Range<usize>,
+  replace_range: Range<usize>,
+  /// Ranges of the string literals the source was read from.
+  literal_ranges: Vec<Range<usize>>,
   source: String,
 }
 
 fn analyze_code_block<'a>(cell: &jsonc_parser::ast::Object<'a>, file_text: &'a str) -> Option<CodeBlockText<'a>> {
   let mut indent_text = "";
-  let mut replace_range = std::ops::Range::default();
-  let mut is_array = false;
-  let cell_source = match &cell.get("source")?.value {
+  let mut replace_range = Range::default();
+  let mut array_range = None;
+  let mut literal_ranges = Vec::new();
+  // nbformat 3 code cells store the source in `input`
+  let source_prop = cell.get("source").or_else(|| cell.get("input"))?;
+  let cell_source = match &source_prop.value {
     jsonc_parser::ast::Value::Array(items) => {
-      is_array = true;
+      array_range = Some(items.range.start..items.range.end);
       let mut strings = Vec::with_capacity(items.elements.len());
       for (i, element) in items.elements.iter().enumerate() {
         let string_lit = element.as_string_lit()?;
@@ -170,8 +720,13 @@ fn analyze_code_block<'a>(cell: &jsonc_parser::ast::Object<'a>, file_text: &'a s
         if i == items.elements.len() - 1 {
           replace_range.end = string_lit.range.end;
         }
+        literal_ranges.push(string_lit.range.start..string_lit.range.end);
         strings.push(&string_lit.value);
       }
+      if items.elements.is_empty() {
+        // write the lines between the brackets
+        replace_range = items.range.start + 1..items.range.end - 1;
+      }
 
       let mut text = String::with_capacity(strings.iter().map(|s| s.len()).sum::<usize>());
       for string in strings {
@@ -181,81 +736,129 @@ fn analyze_code_block<'a>(cell: &jsonc_parser::ast::Object<'a>, file_text: &'a s
     }
     jsonc_parser::ast::Value::StringLit(string) => {
       replace_range = string.range.start..string.range.end;
+      literal_ranges.push(replace_range.clone());
       string.value.to_string()
     }
     _ => return None,
   };
   Some(CodeBlockText {
-    is_array,
+    array_range,
     indent_text,
     replace_range,
+    literal_ranges,
     source: cell_source,
   })
 }
 
-/// Turn the formatted text into a json array, split up by line breaks.
-fn build_array_json_text(formatted_text: &str, indent_text: &str) -> String {
-  let mut new_text = String::new();
-  let mut current_end_index = 0;
-  for (i, line) in formatted_text.split('\n').enumerate() {
-    current_end_index += line.len();
-    if i > 0 {
-      new_text.push_str(",\n");
-      new_text.push_str(indent_text);
-    }
-    let is_last_line = current_end_index == formatted_text.len();
-    new_text.push_str(
-      &serde_json::to_string(
-        if is_last_line {
-          Cow::Borrowed(line)
-        } else {
-          Cow::Owned(format!("{}\n", line))
-        }
-        .as_ref(),
-      )
-      .unwrap(),
-    );
-    current_end_index += 1;
+/// Converts the text's newlines to the provided newline,
+/// returning `None` when they are all the same already.
+pub fn convert_new_lines(text: &str, new_line: &str) -> Option<String> {
+  let lf_text = text.replace("\r\n", "\n");
+  let converted_text = if new_line == "\n" {
+    lf_text
+  } else {
+    lf_text.replace('\n', new_line)
+  };
+  if converted_text == text {
+    None
+  } else {
+    Some(converted_text)
   }
-  new_text
+}
+
+/// Gets if the cell or notebook has `"dprint": { "ignore": true }` in its metadata.
+fn has_ignore_metadata(obj: &jsonc_parser::ast::Object) -> bool {
+  obj
+    .get_object("metadata")
+    .and_then(|metadata| metadata.get_object("dprint"))
+    .and_then(|dprint| dprint.get_boolean("ignore"))
+    .is_some_and(|ignore| ignore.value)
+}
+
+/// Gets if the cell has ignore metadata or the `ignoreTag` tag.
+fn is_cell_ignored(cell: &jsonc_parser::ast::Object, config: &Configuration) -> bool {
+  has_ignore_metadata(cell) || has_ignore_tag(cell, &config.ignore_tag)
+}
+
+fn has_ignore_tag(cell: &jsonc_parser::ast::Object, ignore_tag: &str) -> bool {
+  let Some(tags) = cell
+    .get_object("metadata")
+    .and_then(|metadata| metadata.get_array("tags"))
+  else {
+    return false;
+  };
+  tags
+    .elements
+    .iter()
+    .any(|tag| tag.as_string_lit().is_some_and(|tag| tag.value == ignore_tag))
+}
+
+/// Gets if the first line of the source is a `dprint-ignore` comment
+/// (ex. `# dprint-ignore` or `<!-- dprint-ignore -->`).
+pub fn has_ignore_comment(source: &str) -> bool {
+  let Some(first_line) = source.lines().map(|line| line.trim()).find(|line| !line.is_empty()) else {
+    return false;
+  };
+  let comment_text = if let Some(text) = first_line.strip_prefix("<!--") {
+    text.strip_suffix("-->")
+  } else {
+    ["#", "//", "--"]
+      .into_iter()
+      .find_map(|prefix| first_line.strip_prefix(prefix))
+  };
+  comment_text.is_some_and(|text| text.trim() == "dprint-ignore")
 }
 
 fn get_metadata_language<'a>(root_obj: &'a jsonc_parser::ast::Object<'a>) -> Option<&'a str> {
-  let language_info = root_obj.get_object("metadata")?.get_object("language_info")?;
-  Some(&language_info.get_string("name")?.value)
+  let metadata = root_obj.get_object("metadata")?;
+  let language_info = metadata.get_object("language_info");
+  let kernelspec = metadata.get_object("kernelspec");
+  let polyglot_kernel_info = metadata
+    .get_object("polyglot_notebook")
+    .and_then(|polyglot| polyglot.get_object("kernelInfo"));
+  let get_string = |obj: Option<&'a jsonc_parser::ast::Object<'a>>, name: &str| -> Option<&'a str> {
+    obj?
+      .get_string(name)
+      .map(|value| value.value.as_ref())
+      .filter(|value| !value.is_empty())
+  };
+
+  // polyglot notebooks use "polyglot-notebook" as the language_info name
+  get_string(polyglot_kernel_info, "defaultKernelName")
+    .or_else(|| get_string(language_info, "name"))
+    .or_else(|| get_string(language_info, "file_extension").and_then(file_extension_to_language))
+    .or_else(|| get_string(language_info, "mimetype").and_then(mime_type_to_language))
+    .or_else(|| get_string(kernelspec, "language"))
+    .or_else(|| get_string(kernelspec, "name").and_then(kernel_name_to_language))
 }
 
+/// Gets the language of a cell from the metadata written by Polyglot Notebooks
+/// and VS Code.
 fn get_cell_vscode_language_id<'a>(cell: &'a jsonc_parser::ast::Object<'a>) -> Option<&'a str> {
   let cell_metadata = cell.get_object("metadata")?;
-  let cell_language_info = cell_metadata.get_object("vscode")?;
-  Some(&cell_language_info.get_string("languageId")?.value)
-}
-
-fn language_to_path(language: &str) -> Option<PathBuf> {
-  let ext = match language.to_lowercase().as_str() {
-    "bash" => Some("sh"),
-    "c++" => Some("cpp"),
-    "css" => Some("css"),
-    "csharp" => Some("cs"),
-    "html" => Some("html"),
-    "go" => Some("go"),
-    "kotlin" => Some("kt"),
-    "json" => Some("json"),
-    "julia" => Some("jl"),
-    "markdown" => Some("md"),
-    "typescript" => Some("ts"),
-    "javascript" => Some("js"),
-    "perl" => Some("perl"),
-    "php" => Some("php"),
-    "python" | "python3" => Some("py"),
-    "r" => Some("r"),
-    "ruby" => Some("rb"),
-    "scala" => Some("scala"),
-    "sql" => Some("sql"),
-    "yaml" => Some("yml"),
-    _ => None,
+  let get_string = |obj_name: &str, name: &str| -> Option<&'a str> {
+    let value = cell_metadata.get_object(obj_name)?.get_string(name)?;
+    Some(value.value.as_ref()).filter(|value| !value.is_empty())
   };
-  ext.map(|ext| PathBuf::from(format!("code_block.{}", ext)))
+  // polyglot notebooks set the vscode language id to "polyglot-notebook"
+  get_string("polyglot_notebook", "kernelName")
+    .or_else(|| get_string("dotnet_interactive", "language"))
+    .or_else(|| get_string("vscode", "languageId"))
+}
+
+/// Gets the language of a raw cell from the MIME type nbconvert uses for it
+/// (ex. `text/restructuredtext`), which older notebooks store in `format`.
+fn get_raw_cell_language<'a>(cell: &'a jsonc_parser::ast::Object<'a>) -> Option<&'a str> {
+  let metadata = cell.get_object("metadata")?;
+  ["raw_mimetype", "format"]
+    .into_iter()
+    .filter_map(|name| metadata.get_string(name))
+    .find_map(|mime_type| mime_type_to_language(&mime_type.value))
+}
+
+fn language_to_path(language: &str, config: &Configuration) -> Option<PathBuf> {
+  let ext = resolve_language_extension(language, config)?;
+  Some(PathBuf::from(format!("code_block.{}", ext)))
 }
 
 fn get_indent_text(file_text: &str, start_pos: usize) -> &str {
@@ -271,6 +874,9 @@ fn get_indent_text(file_text: &str, start_pos: usize) -> &str {
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::configuration::OutputConflictResolution;
+  use crate::configuration::default_config;
+  use dprint_core::configuration::NewLineKind;
 
   #[test]
   fn test_get_indent_text() {
@@ -288,7 +894,15 @@ mod test {
     // no changes to code other than bom
     {
       let input_text = "\u{FEFF}{\"cells\":[{\"cell_type\":\"code\",\"source\":\"let x = 5;\"}]}";
-      let formatted_text = format_text(input_text, |_, text| Ok(Some(text))).unwrap().unwrap();
+      let formatted_text = format_text(
+        Path::new("file.ipynb"),
+        input_text,
+        None,
+        &default_config(),
+        |request| Ok(Some(request.file_text)),
+      )
+      .unwrap()
+      .unwrap();
       assert_eq!(
         formatted_text,
         "{\"cells\":[{\"cell_type\":\"code\",\"source\":\"let x = 5;\"}]}"
@@ -307,9 +921,15 @@ mod test {
   }]
 }
 ";
-    let formatted_text = format_text(input_text, |_, text| Ok(Some(format!("{}_formatted", text))))
-      .unwrap()
-      .unwrap();
+    let formatted_text = format_text(
+      Path::new("file.ipynb"),
+      input_text,
+      None,
+      &default_config(),
+      |request| Ok(Some(format!("{}_formatted", request.file_text))),
+    )
+    .unwrap()
+    .unwrap();
     assert_eq!(
       formatted_text,
       "{
@@ -326,4 +946,291 @@ mod test {
 "
     );
   }
+
+  #[test]
+  fn reports_cell_errors() {
+    let input_text = r##"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "source": ["x = 1"]
+  },
+  {
+   "cell_type": "markdown",
+   "source": ["# Title\n", "\n", "bad text"]
+  },
+  {
+   "cell_type": "code",
+   "id": "c3",
+   "source": "a = 1\nb = ("
+  }
+ ],
+ "metadata": {"language_info": {"name": "python"}}
+}"##;
+    let mut config = default_config();
+    let format_with_host = |request: HostFormatRequest| {
+      if request.file_path.ends_with("code_block.md") {
+        anyhow::bail!("Line 3, column 5: Failed parsing.")
+      } else if request.file_text.contains('(') {
+        anyhow::bail!("Failed parsing.")
+      } else {
+        Ok(None)
+      }
+    };
+    assert_eq!(
+      format_text(Path::new("file.ipynb"), input_text, None, &config, format_with_host).unwrap(),
+      None
+    );
+    config.on_cell_error = CellErrorBehavior::Error;
+    let err = format_text(Path::new("file.ipynb"), input_text, None, &config, format_with_host).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      concat!(
+        "Error formatting cell at index 1 (language: markdown) at line 9, column 39: Line 3, column 5: Failed parsing.\n\n",
+        "Error formatting cell at index 2 (id: c3, language: python): Failed parsing."
+      )
+    );
+  }
+
+  #[test]
+  fn trims_trailing_newline() {
+    let input_text = "{\"cells\":[{\"cell_type\":\"code\",\"source\":[\"x = 5\"]}],\"metadata\":{\"language_info\":{\"name\":\"python\"}}}";
+    let format_with_newline = |request: HostFormatRequest| Ok(Some(format!("{}\n", request.file_text)));
+    let mut config = default_config();
+    assert_eq!(
+      format_text(Path::new("file.ipynb"), input_text, None, &config, format_with_newline)
+        .unwrap()
+        .unwrap(),
+      input_text
+    );
+    config.trim_trailing_newline = false;
+    assert_eq!(
+      format_text(Path::new("file.ipynb"), input_text, None, &config, format_with_newline)
+        .unwrap()
+        .unwrap(),
+      "{\"cells\":[{\"cell_type\":\"code\",\"source\":[\"x = 5\\n\"]}],\"metadata\":{\"language_info\":{\"name\":\"python\"}}}"
+    );
+  }
+
+  #[test]
+  fn formats_range() {
+    let input_text = r#"{
+ "cells": [
+  {"cell_type": "code", "source": ["a=1\n", "b=2"]},
+  {"cell_type": "code", "source": "c=3"},
+  {"cell_type": "code", "source": "%%time\nd=4"}
+ ],
+ "metadata": {"language_info": {"name": "python"}}
+}"#;
+    let format = |range: Range<usize>| {
+      let mut requests = Vec::new();
+      let result = format_text(
+        Path::new("file.ipynb"),
+        input_text,
+        Some(range),
+        &default_config(),
+        |request| {
+          requests.push((request.file_text.clone(), request.range));
+          Ok(Some(request.file_text.replace('=', " = ")))
+        },
+      )
+      .unwrap();
+      (result, requests)
+    };
+
+    // within a cell
+    let start = input_text.find("b=2").unwrap();
+    let (result, requests) = format(start..start + 3);
+    assert_eq!(requests, vec![("a=1\nb=2".to_string(), Some(4..7))]);
+    assert_eq!(
+      result.unwrap(),
+      input_text.replace("[\"a=1\\n\", \"b=2\"]", "[\"a = 1\\n\", \"b = 2\"]")
+    );
+
+    // within a cell magic's body
+    let start = input_text.find("d=4").unwrap();
+    let (_, requests) = format(start..start + 3);
+    assert_eq!(requests, vec![("d=4".to_string(), Some(0..3))]);
+
+    // spanning multiple cells
+    let (result, requests) = format(input_text.find("c=3").unwrap()..start);
+    assert_eq!(requests, vec![("c=3".to_string(), None), ("d=4".to_string(), None)]);
+    assert_eq!(
+      result.unwrap(),
+      input_text.replace("\"c=3\"", "\"c = 3\"").replace("d=4", "d = 4")
+    );
+  }
+
+  #[test]
+  fn forwards_override_config() {
+    let input_text = r#"{
+ "cells": [
+  {"cell_type": "code", "source": "x = 1"},
+  {"cell_type": "code", "source": "%%writefile test.ts\nlet y = 2;"},
+  {"cell_type": "markdown", "source": "Text"}
+ ],
+ "metadata": {"language_info": {"name": "python"}}
+}"#;
+    let mut config = default_config();
+    config.overrides.insert(
+      "python".to_string(),
+      ConfigKeyMap::from([("lineWidth".to_string(), 88.into())]),
+    );
+    config.overrides.insert(
+      "typescript".to_string(),
+      ConfigKeyMap::from([("quoteStyle".to_string(), "alwaysSingle".into())]),
+    );
+    let mut requests = Vec::new();
+    format_text(Path::new("file.ipynb"), input_text, None, &config, |request| {
+      requests.push((
+        request.file_path.to_path_buf(),
+        request
+          .override_config
+          .map(|config| config.keys().cloned().collect::<Vec<_>>()),
+      ));
+      Ok(None)
+    })
+    .unwrap();
+    assert_eq!(
+      requests,
+      vec![
+        (PathBuf::from("code_block.py"), Some(vec!["lineWidth".to_string()])),
+        (PathBuf::from("code_block.ts"), Some(vec!["quoteStyle".to_string()])),
+        (PathBuf::from("code_block.md"), None),
+      ]
+    );
+  }
+
+  #[test]
+  fn resolves_output_merge_conflicts() {
+    let input_text = r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+<<<<<<< HEAD
+   "execution_count": 1,
+=======
+   "execution_count": 2,
+>>>>>>> feature
+   "metadata": {},
+   "outputs": [],
+   "source": "x = 1"
+  }
+ ]
+}
+"#;
+    let format = |config: &Configuration| {
+      format_text(Path::new("file.ipynb"), input_text, None, config, |request| {
+        Ok(Some(format!("{}_formatted", request.file_text)))
+      })
+    };
+    let err = format(&default_config()).unwrap_err();
+    assert!(
+      err
+        .to_string()
+        .contains("Conflict at line 5 in cell at index 0 (execution_count)")
+    );
+    let mut config = default_config();
+    config.output_conflicts = OutputConflictResolution::Theirs;
+    config.default_language = Some("python".to_string());
+    assert_eq!(
+      format(&config).unwrap().unwrap(),
+      r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": 2,
+   "metadata": {},
+   "outputs": [],
+   "source": "x = 1_formatted"
+  }
+ ]
+}
+"#
+    );
+    let err = format_text(Path::new("file.ipynb"), input_text, Some(0..10), &config, |_| Ok(None)).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Formatting a range of a notebook with merge conflicts is not supported."
+    );
+  }
+
+  #[test]
+  fn preserves_new_lines() {
+    let input_text = concat!(
+      "{\r\n",
+      " \"cells\": [\r\n",
+      "  {\r\n",
+      "   \"cell_type\": \"code\",\r\n",
+      "   \"source\": [\r\n",
+      "    \"x = 1\\r\\n\",\r\n",
+      "    \"y = 2\"\r\n",
+      "   ]\r\n",
+      "  }\r\n",
+      " ]\r\n",
+      "}\r\n",
+    );
+    let format = |config: &Configuration| {
+      format_text(Path::new("file.ipynb"), input_text, None, config, |request| {
+        // plugins commonly output lf newlines
+        Ok(Some(format!("{}\nz = 3\n", request.file_text.replace("\r\n", "\n"))))
+      })
+      .unwrap()
+      .unwrap()
+    };
+    let mut config = default_config();
+    config.default_language = Some("python".to_string());
+    assert_eq!(
+      format(&config),
+      concat!(
+        "{\r\n",
+        " \"cells\": [\r\n",
+        "  {\r\n",
+        "   \"cell_type\": \"code\",\r\n",
+        "   \"source\": [\r\n",
+        "    \"x = 1\\r\\n\",\r\n",
+        "    \"y = 2\\r\\n\",\r\n",
+        "    \"z = 3\"\r\n",
+        "   ]\r\n",
+        "  }\r\n",
+        " ]\r\n",
+        "}\r\n",
+      )
+    );
+    config.new_line_kind = NewLineKind::LineFeed;
+    assert_eq!(
+      format(&config),
+      concat!(
+        "{\n",
+        " \"cells\": [\n",
+        "  {\n",
+        "   \"cell_type\": \"code\",\n",
+        "   \"source\": [\n",
+        "    \"x = 1\\r\\n\",\n",
+        "    \"y = 2\\r\\n\",\n",
+        "    \"z = 3\"\n",
+        "   ]\n",
+        "  }\n",
+        " ]\n",
+        "}\n",
+      )
+    );
+  }
+
+  #[test]
+  fn preserves_percent_script_new_lines() {
+    let input_text = "# %% [markdown]\r\n# # Title\r\n#\r\n# Text\r\n\r\n# %%\r\nx = 1\r\ny = 2\r\n";
+    let mut config = default_config();
+    config.jupytext_extensions = vec!["py".to_string()];
+    let result = format_text(Path::new("file.py"), input_text, None, &config, |request| {
+      // plugins commonly output lf newlines
+      Ok(Some(format!("{}\nz = 3\n", request.file_text.replace("\r\n", "\n"))))
+    })
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+      result,
+      "# %% [markdown]\r\n# # Title\r\n#\r\n# Text\r\n# z = 3\r\n\r\n# %%\r\nx = 1\r\ny = 2\r\nz = 3\r\n"
+    );
+  }
 }
@@ -0,0 +1,377 @@
+//! IPython extends Python with syntax that Python formatters don't understand
+//! (ex. `%timeit`, `!pip install`, `obj?`). These lines are swapped out for
+//! placeholder statements before formatting and restored afterwards.
+
+const PLACEHOLDER_PREFIX: &str = "__dprint_magic_";
+
+pub struct MaskedSource {
+  /// The source with each IPython specific line replaced by a placeholder.
+  pub text: String,
+  masked_lines: Vec<MaskedLine>,
+}
+
+struct MaskedLine {
+  /// Original text of the line without the trailing newline.
+  text: String,
+  /// Indentation of the first line of the magic this line belongs to.
+  group_indent: String,
+}
+
+impl MaskedSource {
+  /// Restores the original lines in the formatted text.
+  ///
+  /// Returns `None` when the formatter did not keep every placeholder
+  /// on its own line, in which case the result can't be trusted.
+  pub fn unmask(&self, formatted_text: &str) -> Option<String> {
+    if self.masked_lines.is_empty() {
+      return Some(formatted_text.to_string());
+    }
+
+    let mut restored = vec![false; self.masked_lines.len()];
+    let mut text = String::with_capacity(formatted_text.len());
+    for line in formatted_text.split_inclusive('\n') {
+      let (line_text, newline) = split_newline(line);
+      let trimmed = line_text.trim_start();
+      match parse_placeholder(trimmed.trim_end()) {
+        Some(index) if index < restored.len() && !restored[index] => {
+          restored[index] = true;
+          let masked_line = &self.masked_lines[index];
+          let new_indent = &line_text[..line_text.len() - trimmed.len()];
+          text.push_str(new_indent);
+          text.push_str(
+            masked_line
+              .text
+              .strip_prefix(masked_line.group_indent.as_str())
+              .unwrap_or_else(|| masked_line.text.trim_start()),
+          );
+        }
+        Some(_) => return None,
+        None => text.push_str(line_text),
+      }
+      text.push_str(newline);
+    }
+
+    if restored.iter().all(|r| *r) { Some(text) } else { None }
+  }
+}
+
+/// Replaces line magics, shell escapes and help syntax with placeholders.
+///
+/// Returns `None` for cells that can't be handled as Python, such as
+/// cells starting with a `%%` cell magic.
+pub fn mask_ipython_syntax(source: &str) -> Option<MaskedSource> {
+  if is_cell_magic(source) {
+    return None;
+  }
+
+  let lines = source.split_inclusive('\n').collect::<Vec<_>>();
+  let mut state = ScanState::default();
+  let mut masked_lines = Vec::new();
+  let mut text = String::with_capacity(source.len());
+  let mut i = 0;
+  while i < lines.len() {
+    let (line_text, newline) = split_newline(lines[i]);
+    if !state.is_top_level() || !is_ipython_line(line_text) {
+      state.scan_line(line_text);
+      text.push_str(lines[i]);
+      i += 1;
+      continue;
+    }
+
+    // magics continue onto the next line when ending with a backslash
+    let indent = &line_text[..line_text.len() - line_text.trim_start().len()];
+    let mut newline = newline;
+    let mut line_text = line_text;
+    loop {
+      text.push_str(indent);
+      text.push_str(&format!("{}{}__", PLACEHOLDER_PREFIX, masked_lines.len()));
+      text.push_str(newline);
+      masked_lines.push(MaskedLine {
+        text: line_text.to_string(),
+        group_indent: indent.to_string(),
+      });
+      i += 1;
+      if !line_text.trim_end().ends_with('\\') || i >= lines.len() {
+        break;
+      }
+      (line_text, newline) = split_newline(lines[i]);
+    }
+  }
+
+  if !masked_lines.is_empty() && source.contains(PLACEHOLDER_PREFIX) {
+    return None;
+  }
+
+  Some(MaskedSource { text, masked_lines })
+}
+
+/// Gets if the source starts with a `%%` cell magic.
+pub fn is_cell_magic(source: &str) -> bool {
+  source
+    .lines()
+    .find(|line| !line.trim().is_empty())
+    .map(|line| line.trim_start().starts_with("%%"))
+    .unwrap_or(false)
+}
+
+pub struct CellMagic<'a> {
+  /// Text up to and including the `%%` line.
+  pub header: &'a str,
+  pub body: &'a str,
+  pub body_kind: CellMagicBodyKind<'a>,
+}
+
+pub enum CellMagicBodyKind<'a> {
+  Language(&'a str),
+  /// File extension of the file written by `%%writefile`.
+  FileExtension(&'a str),
+}
+
+/// Parses a `%%` cell magic whose body is written in a known language.
+pub fn parse_cell_magic(source: &str) -> Option<CellMagic<'_>> {
+  if !is_cell_magic(source) {
+    return None;
+  }
+  let magic_start = source.find("%%")?;
+  let header_end = source[magic_start..].find('\n').map(|index| magic_start + index + 1)?;
+  let header = &source[..header_end];
+  let mut args = source[magic_start + 2..header_end].split_whitespace();
+  let name = args.next()?;
+  let body_kind = match name {
+    "bash" | "sh" | "sql" | "html" | "javascript" | "markdown" | "perl" | "ruby" | "python" | "python3" => {
+      CellMagicBodyKind::Language(name)
+    }
+    "js" => CellMagicBodyKind::Language("javascript"),
+    "R" => CellMagicBodyKind::Language("r"),
+    // the body is python code run by the magic
+    "capture" | "time" | "timeit" | "prun" | "debug" => CellMagicBodyKind::Language("python"),
+    "script" => {
+      let program = args.find(|arg| !arg.starts_with('-'))?;
+      CellMagicBodyKind::Language(program.rsplit(['/', '\\']).next()?)
+    }
+    "writefile" | "file" => {
+      let file_name = args.rfind(|arg| !arg.starts_with('-'))?;
+      let (_, ext) = file_name.trim_matches(['"', '\'']).rsplit_once('.')?;
+      CellMagicBodyKind::FileExtension(ext)
+    }
+    _ => return None,
+  };
+  Some(CellMagic {
+    header,
+    body: &source[header_end..],
+    body_kind,
+  })
+}
+
+fn is_ipython_line(line_text: &str) -> bool {
+  let trimmed = line_text.trim();
+  if trimmed.is_empty() || trimmed.starts_with('#') {
+    return false;
+  }
+  // line magics, shell escapes, help and autocall escapes
+  if trimmed.starts_with(['%', '!', '?', '/', ',', ';']) {
+    return true;
+  }
+  // ex. `files = !ls` or `result = %timeit -o fn()`
+  if let Some(rhs) = get_assignment_rhs(trimmed) {
+    let rhs = rhs.trim_start();
+    if rhs.starts_with('!') || (rhs.starts_with('%') && !rhs.starts_with("%=")) {
+      return true;
+    }
+  }
+  // help syntax (ex. `obj?` or `obj??`)
+  let mut state = ScanState::default();
+  state.scan_line(line_text);
+  line_text[..state.code_end].trim_end().ends_with('?')
+}
+
+fn get_assignment_rhs(text: &str) -> Option<&str> {
+  let index = text.find('=')?;
+  let lhs = &text[..index];
+  let is_simple_target = !lhs.trim().is_empty()
+    && lhs
+      .chars()
+      .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | ',' | ' ' | '\t'));
+  if is_simple_target && !text[index + 1..].starts_with('=') {
+    Some(&text[index + 1..])
+  } else {
+    None
+  }
+}
+
+fn parse_placeholder(text: &str) -> Option<usize> {
+  text.strip_prefix(PLACEHOLDER_PREFIX)?.strip_suffix("__")?.parse().ok()
+}
+
+fn split_newline(line: &str) -> (&str, &str) {
+  if let Some(text) = line.strip_suffix("\r\n") {
+    (text, "\r\n")
+  } else if let Some(text) = line.strip_suffix('\n') {
+    (text, "\n")
+  } else {
+    (line, "")
+  }
+}
+
+/// Very small Python tokenizer that only tracks what's necessary to know
+/// whether a line starts a new statement.
+#[derive(Default)]
+struct ScanState {
+  /// The quote of the string that's currently open across lines.
+  open_string: Option<&'static str>,
+  bracket_depth: usize,
+  line_continuation: bool,
+  /// Byte index where the code ends on the last scanned line (start of a comment).
+  code_end: usize,
+}
+
+impl ScanState {
+  fn is_top_level(&self) -> bool {
+    self.open_string.is_none() && self.bracket_depth == 0 && !self.line_continuation
+  }
+
+  fn scan_line(&mut self, line_text: &str) {
+    let bytes = line_text.as_bytes();
+    let mut i = 0;
+    self.code_end = line_text.len();
+    while i < bytes.len() {
+      if let Some(quote) = self.open_string {
+        if bytes[i] == b'\\' {
+          i += 2;
+        } else if bytes[i..].starts_with(quote.as_bytes()) {
+          self.open_string = None;
+          i += quote.len();
+        } else {
+          i += 1;
+        }
+        continue;
+      }
+      match bytes[i] {
+        b'#' => {
+          self.code_end = i;
+          break;
+        }
+        b'(' | b'[' | b'{' => self.bracket_depth += 1,
+        b')' | b']' | b'}' => self.bracket_depth = self.bracket_depth.saturating_sub(1),
+        b'"' | b'\'' => {
+          let quote = ["\"\"\"", "'''", "\"", "'"]
+            .into_iter()
+            .find(|quote| bytes[i..].starts_with(quote.as_bytes()))
+            .unwrap();
+          self.open_string = Some(quote);
+          i += quote.len();
+          continue;
+        }
+        _ => {}
+      }
+      i += 1;
+    }
+
+    // single quoted strings can't span lines without a backslash
+    if matches!(self.open_string, Some("\"") | Some("'")) && !line_text.ends_with('\\') {
+      self.open_string = None;
+    }
+    self.line_continuation = line_text[..self.code_end].ends_with('\\');
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn mask(text: &str) -> String {
+    mask_ipython_syntax(text).unwrap().text
+  }
+
+  #[test]
+  fn masks_ipython_lines() {
+    assert_eq!(
+      mask("%matplotlib inline\nimport os\n!pip install dprint\n"),
+      "__dprint_magic_0__\nimport os\n__dprint_magic_1__\n"
+    );
+    assert_eq!(
+      mask("os.path?\n??os\nx = 1 # why?"),
+      "__dprint_magic_0__\n__dprint_magic_1__\nx = 1 # why?"
+    );
+    assert_eq!(
+      mask("files = !ls\nx = %time fn()\nx == 1\nx %= 2"),
+      "__dprint_magic_0__\n__dprint_magic_1__\nx == 1\nx %= 2"
+    );
+    assert_eq!(
+      mask("if True:\n  !echo \\\n    test\n"),
+      "if True:\n  __dprint_magic_0__\n  __dprint_magic_1__\n"
+    );
+    assert_eq!(mask("x = (1\n  % 2)"), "x = (1\n  % 2)");
+    assert_eq!(mask("x = \"\"\"\n!test\n\"\"\""), "x = \"\"\"\n!test\n\"\"\"");
+    assert_eq!(mask("x = 'it''s'\n!test"), "x = 'it''s'\n__dprint_magic_0__");
+    assert_eq!(mask("x = '\\é'\n!test"), "x = '\\é'\n__dprint_magic_0__");
+  }
+
+  #[test]
+  fn skips_cell_magics() {
+    assert!(mask_ipython_syntax("%%bash\necho 1").is_none());
+    assert!(mask_ipython_syntax("\n  %%timeit\nfn()").is_none());
+  }
+
+  #[test]
+  fn parses_cell_magics() {
+    fn parse(text: &str) -> Option<(&str, &str, String)> {
+      let cell_magic = parse_cell_magic(text)?;
+      let kind = match cell_magic.body_kind {
+        CellMagicBodyKind::Language(language) => format!("language:{}", language),
+        CellMagicBodyKind::FileExtension(ext) => format!("ext:{}", ext),
+      };
+      Some((cell_magic.header, cell_magic.body, kind))
+    }
+
+    assert_eq!(
+      parse("%%sql\nSELECT 1"),
+      Some(("%%sql\n", "SELECT 1", "language:sql".to_string()))
+    );
+    assert_eq!(
+      parse("\n%%writefile -a src/mod.ts\nlet a"),
+      Some(("\n%%writefile -a src/mod.ts\n", "let a", "ext:ts".to_string()))
+    );
+    assert_eq!(
+      parse("%%script /usr/bin/bash --no-raise-error\necho"),
+      Some((
+        "%%script /usr/bin/bash --no-raise-error\n",
+        "echo",
+        "language:bash".to_string()
+      ))
+    );
+    assert_eq!(
+      parse("%%timeit -n 5\nfn()"),
+      Some(("%%timeit -n 5\n", "fn()", "language:python".to_string()))
+    );
+    assert_eq!(parse("%%latex\n\\alpha"), None);
+    assert_eq!(parse("%%writefile Makefile\nall:"), None);
+    assert_eq!(parse("%%bash"), None);
+    assert_eq!(parse("x = 5"), None);
+  }
+
+  #[test]
+  fn skips_existing_placeholders() {
+    assert!(mask_ipython_syntax("__dprint_magic_0__\n!ls").is_none());
+    assert_eq!(mask("__dprint_magic_0__"), "__dprint_magic_0__");
+  }
+
+  #[test]
+  fn unmasks_formatted_text() {
+    let masked = mask_ipython_syntax("if True:\n  !echo \\\n    test\n  %time fn( )\n").unwrap();
+    assert_eq!(
+      masked
+        .unmask("if True:\n    __dprint_magic_0__\n    __dprint_magic_1__\n    __dprint_magic_2__\n")
+        .unwrap(),
+      "if True:\n    !echo \\\n      test\n    %time fn( )\n"
+    );
+    // placeholder was lost
+    assert!(masked.unmask("if True:\n    __dprint_magic_0__\n").is_none());
+    // placeholder was changed
+    assert!(
+      masked
+        .unmask("if True:\n    __dprint_magic_0__\n    __dprint_magic_1__\n    __dprint_magic_2___python\n")
+        .is_none()
+    );
+  }
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use crate::configuration::Configuration;
+use crate::languages::file_extension_to_language;
+use crate::text_cells::TextCell;
+use crate::text_cells::get_line_comment;
+use crate::text_cells::get_lines;
+use crate::text_cells::get_lines_range;
+
+/// Gets the language of the file when it's a Jupytext percent script
+/// based on the `jupytextExtensions` configuration.
+pub fn get_percent_script_language(file_path: &Path, config: &Configuration) -> Option<&'static str> {
+  let file_name = file_path.file_name()?.to_str()?.to_lowercase();
+  let is_percent_script = config
+    .jupytext_extensions
+    .iter()
+    .any(|ext| file_name.ends_with(&format!(".{}", ext.to_lowercase())));
+  if !is_percent_script {
+    return None;
+  }
+  let ext = file_name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or_default();
+  Some(file_extension_to_language(ext).unwrap_or("python"))
+}
+
+/// Gets the cells of a Jupytext percent script, which separates cells
+/// with `# %%` comments and comments out the text of markdown cells.
+///
+/// See https://jupytext.readthedocs.io/en/latest/formats-scripts.html#the-percent-format
+pub fn parse_percent_script<'a>(text: &'a str, language: &'a str, config: &Configuration) -> Vec<TextCell<'a>> {
+  let comment = get_line_comment(language);
+  let lines = get_lines(text);
+
+  // skip over the yaml header
+  let is_header_delimiter = |line: &str| line.trim_end() == format!("{} ---", comment);
+  let start_index = match lines.first() {
+    Some((_, line)) if is_header_delimiter(line) => lines
+      .iter()
+      .skip(1)
+      .position(|(_, line)| is_header_delimiter(line))
+      .map(|index| index + 2)
+      .unwrap_or(lines.len()),
+    _ => 0,
+  };
+
+  let mut cells = Vec::new();
+  let mut index = 0;
+  let mut marker = None;
+  let mut content_start = start_index;
+  for i in start_index..=lines.len() {
+    let next_marker = lines.get(i).and_then(|(_, line)| parse_cell_marker(line, comment));
+    if i < lines.len() && next_marker.is_none() {
+      continue;
+    }
+    let content_lines = &lines[content_start..i];
+    let first = content_lines.iter().position(|(_, line)| !line.trim().is_empty());
+    let last = content_lines.iter().rposition(|(_, line)| !line.trim().is_empty());
+    if let (Some(first), Some(last)) = (first, last) {
+      let range = get_lines_range(&content_lines[first..=last]);
+      if let Some(cell) = get_cell(marker, index, range, language, comment, config) {
+        cells.push(cell);
+      }
+      index += 1;
+    } else if marker.is_some() {
+      index += 1;
+    }
+    marker = next_marker;
+    content_start = i + 1;
+  }
+  cells
+}
+
+/// Gets the text after the `%%` of a cell marker line.
+fn parse_cell_marker<'a>(line: &'a str, comment: &str) -> Option<&'a str> {
+  line.strip_prefix(comment)?.trim_start().strip_prefix("%%")
+}
+
+fn get_cell<'a>(
+  marker: Option<&str>,
+  index: usize,
+  range: std::ops::Range<usize>,
+  language: &'a str,
+  comment: &str,
+  config: &Configuration,
+) -> Option<TextCell<'a>> {
+  let marker = marker.unwrap_or_default();
+  if marker.contains(&format!("\"{}\"", config.ignore_tag)) {
+    return None;
+  }
+  if marker.contains("[markdown]") || marker.contains("[md]") {
+    config.format_markdown_cells.then(|| TextCell {
+      index,
+      language: "markdown",
+      range,
+      line_prefix: Some(format!("{} ", comment)),
+    })
+  } else if marker.contains("[raw]") {
+    None
+  } else {
+    config.format_code_cells.then_some(TextCell {
+      index,
+      language,
+      range,
+      line_prefix: None,
+    })
+  }
+}
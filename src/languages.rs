@@ -0,0 +1,190 @@
+use crate::configuration::Configuration;
+
+/// Built-in mapping of lowercase notebook language ids to the file
+/// extension used when formatting code in that language.
+pub const LANGUAGE_EXTENSIONS: &[(&str, &str)] = &[
+  ("bash", "sh"),
+  ("c", "c"),
+  ("c#", "cs"),
+  ("c++", "cpp"),
+  ("cpp", "cpp"),
+  ("csharp", "cs"),
+  ("css", "css"),
+  ("dart", "dart"),
+  ("dockerfile", "dockerfile"),
+  ("f#", "fs"),
+  ("fsharp", "fs"),
+  ("go", "go"),
+  ("graphql", "graphql"),
+  ("haskell", "hs"),
+  ("html", "html"),
+  ("ipython", "py"),
+  ("ipython3", "py"),
+  ("java", "java"),
+  ("javascript", "js"),
+  ("json", "json"),
+  ("julia", "jl"),
+  ("kotlin", "kt"),
+  ("latex", "tex"),
+  ("less", "less"),
+  ("lua", "lua"),
+  ("markdown", "md"),
+  ("perl", "perl"),
+  ("php", "php"),
+  ("powershell", "ps1"),
+  ("pwsh", "ps1"),
+  ("pyspark", "py"),
+  ("python", "py"),
+  ("python3", "py"),
+  ("r", "r"),
+  ("restructuredtext", "rst"),
+  ("ruby", "rb"),
+  ("rust", "rs"),
+  ("scala", "scala"),
+  ("scss", "scss"),
+  ("sh", "sh"),
+  ("shell", "sh"),
+  ("sql", "sql"),
+  ("swift", "swift"),
+  ("toml", "toml"),
+  ("typescript", "ts"),
+  ("xml", "xml"),
+  ("yaml", "yml"),
+  ("zsh", "sh"),
+];
+
+/// Gets the file extension for a language id from the built-in mapping.
+pub fn language_to_extension(language: &str) -> Option<&'static str> {
+  let language = language.to_lowercase();
+  LANGUAGE_EXTENSIONS
+    .iter()
+    .find(|(id, _)| *id == language)
+    .map(|(_, ext)| *ext)
+}
+
+/// Gets the file extension for a language id, preferring the
+/// `languageExtensions` configuration over the built-in mapping.
+pub fn resolve_language_extension<'a>(language: &str, config: &'a Configuration) -> Option<&'a str> {
+  match config.language_extensions.get(&language.to_lowercase()) {
+    Some(ext) => ext.as_deref(),
+    None => language_to_extension(language),
+  }
+}
+
+/// Gets the language id for a file extension (ex. `".py"`).
+pub fn file_extension_to_language(ext: &str) -> Option<&'static str> {
+  let ext = ext.trim_start_matches('.').to_lowercase();
+  let language = match ext.as_str() {
+    "cs" => "csharp",
+    "fs" | "fsx" => "fsharp",
+    "jl" => "julia",
+    "js" | "mjs" | "cjs" => "javascript",
+    "kt" => "kotlin",
+    "md" => "markdown",
+    "ps1" => "powershell",
+    "py" => "python",
+    "rb" => "ruby",
+    "rst" => "restructuredtext",
+    "rs" => "rust",
+    "sh" => "bash",
+    "tex" => "latex",
+    "ts" | "mts" | "cts" => "typescript",
+    "yml" | "yaml" => "yaml",
+    _ => {
+      return LANGUAGE_EXTENSIONS
+        .iter()
+        .find(|(id, e)| *id == ext && *e == ext)
+        .map(|(id, _)| *id);
+    }
+  };
+  Some(language)
+}
+
+/// Gets the language id for a MIME type (ex. `"text/x-python"`).
+pub fn mime_type_to_language(mime_type: &str) -> Option<&'static str> {
+  let language = match mime_type.trim().to_lowercase().as_str() {
+    "application/javascript" | "text/javascript" => "javascript",
+    "application/json" => "json",
+    "application/julia" | "text/x-julia" => "julia",
+    "application/x-yaml" | "text/x-yaml" | "text/yaml" => "yaml",
+    "text/css" => "css",
+    "text/html" => "html",
+    "text/latex" | "text/x-latex" | "application/x-latex" => "latex",
+    "text/markdown" | "text/x-markdown" => "markdown",
+    "text/restructuredtext" | "text/x-rst" => "restructuredtext",
+    "text/typescript" | "text/x-typescript" | "application/typescript" => "typescript",
+    "text/x-csharp" => "csharp",
+    "text/x-fsharp" => "fsharp",
+    "text/x-go" => "go",
+    "text/x-kotlin" => "kotlin",
+    "text/x-python" | "text/x-ipython" => "python",
+    "text/x-r-source" | "text/x-rsrc" => "r",
+    "text/x-rustsrc" => "rust",
+    "text/x-scala" => "scala",
+    "text/x-sh" => "bash",
+    "text/x-sql" => "sql",
+    _ => return None,
+  };
+  Some(language)
+}
+
+/// Guesses the language id from a Jupyter kernel name (ex. `"python3"` or `"julia-1.9"`).
+pub fn kernel_name_to_language(kernel_name: &str) -> Option<&'static str> {
+  let name = kernel_name.to_lowercase();
+  let language = match name.as_str() {
+    "ir" => "r",
+    "deno" | "tslab" => "typescript",
+    "ijavascript" | "jslab" | "nodejs" => "javascript",
+    "evcxr_jupyter" => "rust",
+    "gophernotes" => "go",
+    // polyglot notebook kernels and their aliases
+    ".net-csharp" | "c#" | "cs" => "csharp",
+    ".net-fsharp" | "f#" | "fs" => "fsharp",
+    ".net-powershell" | "pwsh" => "powershell",
+    "js" => "javascript",
+    "md" => "markdown",
+    "sparkkernel" => "scala",
+    "sparkrkernel" => "r",
+    _ if name.starts_with("pyspark") || name.ends_with("-py") => "python",
+    _ => {
+      // strip versions and other suffixes (ex. `python3`, `julia-1.9`)
+      let base_name = name.split(|c: char| !c.is_ascii_alphabetic()).next()?;
+      return LANGUAGE_EXTENSIONS
+        .iter()
+        .find(|(id, _)| *id == base_name)
+        .map(|(id, _)| *id);
+    }
+  };
+  Some(language)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn gets_language_extension() {
+    assert_eq!(language_to_extension("Python"), Some("py"));
+    assert_eq!(language_to_extension("unknown"), None);
+    // keep the table sorted so it's easy to find entries
+    assert!(LANGUAGE_EXTENSIONS.windows(2).all(|w| w[0].0 < w[1].0));
+  }
+
+  #[test]
+  fn gets_language_from_other_sources() {
+    assert_eq!(file_extension_to_language(".py"), Some("python"));
+    assert_eq!(file_extension_to_language("scala"), Some("scala"));
+    assert_eq!(file_extension_to_language(".unknown"), None);
+    assert_eq!(mime_type_to_language("text/x-python"), Some("python"));
+    assert_eq!(mime_type_to_language("text/restructuredtext"), Some("restructuredtext"));
+    assert_eq!(mime_type_to_language("text/plain"), None);
+    assert_eq!(kernel_name_to_language("python3"), Some("python"));
+    assert_eq!(kernel_name_to_language("julia-1.9"), Some("julia"));
+    assert_eq!(kernel_name_to_language("ir"), Some("r"));
+    assert_eq!(kernel_name_to_language("deno"), Some("typescript"));
+    assert_eq!(kernel_name_to_language("conda-env-analysis-py"), Some("python"));
+    assert_eq!(kernel_name_to_language("C#"), Some("csharp"));
+    assert_eq!(kernel_name_to_language("pwsh"), Some("powershell"));
+    assert_eq!(kernel_name_to_language("unknown"), None);
+  }
+}
@@ -1,8 +1,30 @@
+mod canonical_json;
+mod cell_error;
+mod cell_ids;
 pub mod configuration;
+mod databricks;
+mod dib;
+mod fenced_code;
 mod format_text;
+mod ipython_magics;
+mod jupytext;
+mod languages;
+mod markdown_code_blocks;
+mod merge_conflicts;
+mod multiline_strings;
+mod quarto;
+mod source_map;
+mod strip_outputs;
+mod text_cells;
 mod text_changes;
+mod upgrade;
+mod validate_schema;
 
+pub use format_text::HostFormatRequest;
 pub use format_text::format_text;
+pub use languages::LANGUAGE_EXTENSIONS;
+pub use languages::language_to_extension;
+pub use languages::resolve_language_extension;
 
 #[cfg(feature = "wasm")]
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
@@ -0,0 +1,59 @@
+use crate::configuration::Configuration;
+use crate::fenced_code::parse_fenced_code_blocks;
+use crate::languages::file_extension_to_language;
+use crate::languages::resolve_language_extension;
+use crate::text_cells::TextCell;
+
+/// Gets the fenced code blocks of a markdown cell's source that have a
+/// language (ex. a block starting with ```` ```python ````).
+pub fn parse_markdown_code_blocks<'a>(text: &'a str, config: &Configuration) -> Vec<TextCell<'a>> {
+  parse_fenced_code_blocks(text)
+    .into_iter()
+    .filter_map(|block| Some((get_code_block_language(block.info, config)?, block)))
+    .enumerate()
+    .filter_map(|(index, (language, block))| {
+      if text[block.range.clone()].trim().is_empty() {
+        return None;
+      }
+      Some(TextCell {
+        index,
+        language,
+        range: block.range,
+        line_prefix: (!block.indent.is_empty()).then(|| block.indent.to_string()),
+      })
+    })
+    .collect()
+}
+
+/// Gets the language from the fence's info string, which is either a
+/// language id or a file extension (ex. `python`, `py` or `{.python}`).
+fn get_code_block_language<'a>(info: &'a str, config: &Configuration) -> Option<&'a str> {
+  let word = info
+    .split(|c: char| c.is_whitespace() || c == ',' || c == '}')
+    .next()?
+    .trim_start_matches(['{', '.']);
+  if word.is_empty() {
+    None
+  } else if resolve_language_extension(word, config).is_some() {
+    Some(word)
+  } else {
+    file_extension_to_language(word)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::configuration::default_config;
+
+  #[test]
+  fn gets_code_block_language() {
+    let config = default_config();
+    assert_eq!(get_code_block_language("python", &config), Some("python"));
+    assert_eq!(get_code_block_language("py title=\"a.py\"", &config), Some("python"));
+    assert_eq!(get_code_block_language("{.ts}", &config), Some("typescript"));
+    assert_eq!(get_code_block_language("{python}", &config), Some("python"));
+    assert_eq!(get_code_block_language("unknown", &config), None);
+    assert_eq!(get_code_block_language("", &config), None);
+  }
+}
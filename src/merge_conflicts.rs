@@ -0,0 +1,277 @@
+use std::ops::Range;
+
+use jsonc_parser::ast::Value;
+use jsonc_parser::common::Ranged;
+
+use crate::configuration::OutputConflictResolution;
+use crate::format_text::parse_notebook;
+
+/// Cell properties that are regenerated when running a notebook, so
+/// their conflicts can be resolved by taking one side.
+const OUTPUT_PROPERTIES: &[&str] = &["execution_count", "outputs"];
+
+/// A conflict left by git in the notebook's text.
+struct MergeConflict {
+  /// Line of the `<<<<<<<` marker.
+  line: usize,
+  /// Range of the conflict in the notebook that takes our side of every conflict.
+  ours_range: Range<usize>,
+  /// Range of the conflict in the notebook that takes their side of every conflict.
+  theirs_range: Range<usize>,
+}
+
+/// A notebook's text with every conflict resolved to our side
+/// and with every conflict resolved to their side.
+struct ConflictedNotebook {
+  ours_text: String,
+  theirs_text: String,
+  conflicts: Vec<MergeConflict>,
+}
+
+/// Where a conflict is in the notebook.
+struct ConflictLocation {
+  cell_index: Option<usize>,
+  /// Names of the cell properties the conflict changes.
+  properties: Vec<String>,
+}
+
+impl ConflictLocation {
+  fn is_output_only(&self) -> bool {
+    self.cell_index.is_some()
+      && !self.properties.is_empty()
+      && self
+        .properties
+        .iter()
+        .all(|name| OUTPUT_PROPERTIES.contains(&name.as_str()))
+  }
+}
+
+pub fn has_merge_conflict_markers(text: &str) -> bool {
+  text.lines().any(|line| line.starts_with("<<<<<<<"))
+}
+
+/// Resolves the notebook's merge conflicts when they are all in the outputs or
+/// execution counts of cells and the configuration says which side to take.
+///
+/// Otherwise, returns an error that lists the conflicts and their cells.
+pub fn resolve_merge_conflicts(text: &str, resolution: OutputConflictResolution) -> anyhow::Result<String> {
+  let Some(notebook) = parse_conflicts(text) else {
+    anyhow::bail!("The notebook has merge conflict markers that could not be parsed.");
+  };
+  let ours_value = parse_notebook(&notebook.ours_text).ok().and_then(|result| result.value);
+  let theirs_value = parse_notebook(&notebook.theirs_text)
+    .ok()
+    .and_then(|result| result.value);
+  let locations = notebook
+    .conflicts
+    .iter()
+    .map(|conflict| {
+      let ours_location = get_conflict_location(ours_value.as_ref(), &conflict.ours_range);
+      let theirs_location = get_conflict_location(theirs_value.as_ref(), &conflict.theirs_range);
+      match (ours_location, theirs_location) {
+        (Some(ours), Some(theirs)) if ours.cell_index == theirs.cell_index => {
+          let mut properties = ours.properties;
+          for name in theirs.properties {
+            if !properties.contains(&name) {
+              properties.push(name);
+            }
+          }
+          ConflictLocation {
+            cell_index: ours.cell_index,
+            properties,
+          }
+        }
+        (Some(location), None) | (None, Some(location)) => location,
+        _ => ConflictLocation {
+          cell_index: None,
+          properties: Vec::new(),
+        },
+      }
+    })
+    .collect::<Vec<_>>();
+
+  if resolution != OutputConflictResolution::Error && locations.iter().all(|location| location.is_output_only()) {
+    return Ok(match resolution {
+      OutputConflictResolution::Theirs => notebook.theirs_text,
+      _ => notebook.ours_text,
+    });
+  }
+
+  let mut message = "The notebook has unresolved merge conflicts:".to_string();
+  for (conflict, location) in notebook.conflicts.iter().zip(&locations) {
+    message.push_str(&format!("\n  Conflict at line {}", conflict.line));
+    if let Some(cell_index) = location.cell_index {
+      message.push_str(&format!(" in cell at index {}", cell_index));
+      if !location.properties.is_empty() {
+        message.push_str(&format!(" ({})", location.properties.join(", ")));
+      }
+    }
+  }
+  if resolution == OutputConflictResolution::Error && locations.iter().any(|location| location.is_output_only()) {
+    message.push_str("\n\nConflicts in outputs and execution counts can be resolved by setting \"outputConflicts\" to \"ours\" or \"theirs\".");
+  }
+  anyhow::bail!("{}", message)
+}
+
+/// Splits the text at the conflict markers into our and their version of
+/// the notebook. A `|||||||` section with the merge base is dropped.
+///
+/// Returns `None` when a conflict is missing one of its markers.
+fn parse_conflicts(text: &str) -> Option<ConflictedNotebook> {
+  enum Section {
+    Both,
+    Ours,
+    Base,
+    Theirs,
+  }
+
+  let mut notebook = ConflictedNotebook {
+    ours_text: String::new(),
+    theirs_text: String::new(),
+    conflicts: Vec::new(),
+  };
+  let mut section = Section::Both;
+  let mut conflict_start = (0, 0);
+  for (line_index, line) in text.split_inclusive('\n').enumerate() {
+    match section {
+      Section::Both if line.starts_with("<<<<<<<") => {
+        section = Section::Ours;
+        notebook.conflicts.push(MergeConflict {
+          line: line_index + 1,
+          ours_range: 0..0,
+          theirs_range: 0..0,
+        });
+        conflict_start = (notebook.ours_text.len(), notebook.theirs_text.len());
+      }
+      Section::Ours | Section::Base if line.starts_with("=======") => section = Section::Theirs,
+      Section::Ours if line.starts_with("|||||||") => section = Section::Base,
+      Section::Theirs if line.starts_with(">>>>>>>") => {
+        section = Section::Both;
+        let conflict = notebook.conflicts.last_mut().unwrap();
+        conflict.ours_range = conflict_start.0..notebook.ours_text.len();
+        conflict.theirs_range = conflict_start.1..notebook.theirs_text.len();
+      }
+      Section::Both => {
+        notebook.ours_text.push_str(line);
+        notebook.theirs_text.push_str(line);
+      }
+      Section::Ours => notebook.ours_text.push_str(line),
+      Section::Base => {}
+      Section::Theirs => notebook.theirs_text.push_str(line),
+    }
+  }
+  match section {
+    Section::Both if !notebook.conflicts.is_empty() => Some(notebook),
+    _ => None,
+  }
+}
+
+/// Gets the cell a conflict is in and the properties of the cell that it changes.
+///
+/// Returns `None` when the notebook doesn't parse or the conflict is empty on this side.
+fn get_conflict_location(root_value: Option<&Value>, range: &Range<usize>) -> Option<ConflictLocation> {
+  if range.is_empty() {
+    return None;
+  }
+  let cells = root_value?.as_object()?.get_array("cells")?;
+  let overlaps = |start: usize, end: usize| start < range.end && range.start < end;
+  let Some((cell_index, cell)) = cells
+    .elements
+    .iter()
+    .enumerate()
+    .find(|(_, cell)| cell.start() <= range.start && range.end <= cell.end())
+  else {
+    return Some(ConflictLocation {
+      cell_index: None,
+      properties: Vec::new(),
+    });
+  };
+  let properties = cell
+    .as_object()
+    .map(|cell| {
+      cell
+        .properties
+        .iter()
+        .filter(|prop| overlaps(prop.range.start, prop.range.end))
+        .map(|prop| prop.name.as_str().to_string())
+        .collect()
+    })
+    .unwrap_or_default();
+  Some(ConflictLocation {
+    cell_index: Some(cell_index),
+    properties,
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn resolve(text: &str, resolution: OutputConflictResolution) -> Result<String, String> {
+    resolve_merge_conflicts(text, resolution).map_err(|err| err.to_string())
+  }
+
+  const OUTPUT_CONFLICT: &str = r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+<<<<<<< HEAD
+   "execution_count": 1,
+=======
+   "execution_count": 2,
+>>>>>>> other
+   "metadata": {},
+   "outputs": [],
+   "source": "x = 1"
+  }
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 2
+}
+"#;
+
+  #[test]
+  fn resolves_output_conflicts() {
+    assert_eq!(
+      resolve(OUTPUT_CONFLICT, OutputConflictResolution::Ours).unwrap(),
+      OUTPUT_CONFLICT
+        .replace("<<<<<<< HEAD\n", "")
+        .replace("=======\n   \"execution_count\": 2,\n>>>>>>> other\n", "")
+    );
+    assert_eq!(
+      resolve(OUTPUT_CONFLICT, OutputConflictResolution::Theirs).unwrap(),
+      OUTPUT_CONFLICT
+        .replace("<<<<<<< HEAD\n   \"execution_count\": 1,\n=======\n", "")
+        .replace(">>>>>>> other\n", "")
+    );
+    assert_eq!(
+      resolve(OUTPUT_CONFLICT, OutputConflictResolution::Error).unwrap_err(),
+      concat!(
+        "The notebook has unresolved merge conflicts:\n",
+        "  Conflict at line 5 in cell at index 0 (execution_count)\n\n",
+        "Conflicts in outputs and execution counts can be resolved by setting \"outputConflicts\" to \"ours\" or \"theirs\"."
+      )
+    );
+  }
+
+  #[test]
+  fn reports_source_conflicts() {
+    let text = OUTPUT_CONFLICT.replace(
+      "   \"source\": \"x = 1\"\n",
+      "<<<<<<< HEAD\n   \"source\": \"x = 1\"\n||||||| base\n   \"source\": \"x\"\n=======\n   \"source\": \"x = 2\"\n>>>>>>> other\n",
+    );
+    assert_eq!(
+      resolve(&text, OutputConflictResolution::Ours).unwrap_err(),
+      concat!(
+        "The notebook has unresolved merge conflicts:\n",
+        "  Conflict at line 5 in cell at index 0 (execution_count)\n",
+        "  Conflict at line 12 in cell at index 0 (source)"
+      )
+    );
+    assert_eq!(
+      resolve("{\n<<<<<<< HEAD\n", OutputConflictResolution::Ours).unwrap_err(),
+      "The notebook has merge conflict markers that could not be parsed."
+    );
+  }
+}
@@ -0,0 +1,202 @@
+use jsonc_parser::ast::Object;
+use jsonc_parser::ast::Value;
+use jsonc_parser::common::Ranged;
+
+use crate::configuration::Configuration;
+use crate::configuration::MultilineStringStyle;
+use crate::text_changes::TextChange;
+
+/// Output data that Jupyter splits into lines, along with the `text/` mime types.
+const SPLIT_OUTPUT_MIME_TYPES: &[&str] = &["application/javascript", "image/svg+xml"];
+
+/// Turn the text into the elements of a json array, split up by line breaks.
+///
+/// Empty text has no elements, like Jupyter writes it.
+pub fn build_array_json_text(text: &str, separator: &str) -> String {
+  let mut new_text = String::new();
+  for (i, line) in text.split_inclusive('\n').enumerate() {
+    if i > 0 {
+      new_text.push_str(separator);
+    }
+    new_text.push_str(&serde_json::to_string(line).unwrap());
+  }
+  new_text
+}
+
+/// Turn the text into a json array with each line on its own line, indented
+/// one level past the line that contains the position the array starts at.
+pub fn build_expanded_array_json_text(
+  file_text: &str,
+  pos: usize,
+  text: &str,
+  config: &Configuration,
+  new_line: &str,
+) -> String {
+  if text.is_empty() {
+    return "[]".to_string();
+  }
+  let line_indent_text = get_line_indent_text(file_text, pos);
+  let indent_text = format!("{}{}", line_indent_text, get_indent_unit_text(config));
+  format!(
+    "[{}{}{}{}{}]",
+    new_line,
+    indent_text,
+    build_array_json_text(text, &format!(",{}{}", new_line, indent_text)),
+    new_line,
+    line_indent_text
+  )
+}
+
+/// Gets the text changes that convert the cell's multiline output
+/// text and data to the `outputTextStyle`.
+pub fn get_output_text_changes(
+  file_text: &str,
+  cell: &Object,
+  config: &Configuration,
+  new_line: &str,
+) -> Vec<TextChange> {
+  let mut text_changes = Vec::new();
+  if config.output_text_style == MultilineStringStyle::Preserve {
+    return text_changes;
+  }
+  let Some(outputs) = cell.get_array("outputs") else {
+    return text_changes;
+  };
+  // the outputs are replaced when stripping them
+  if config.strip_outputs && !outputs.elements.is_empty() {
+    return text_changes;
+  }
+  for output in outputs.elements.iter().filter_map(|output| output.as_object()) {
+    let values = output.get("text").map(|prop| &prop.value).into_iter().chain(
+      output
+        .get_object("data")
+        .into_iter()
+        .flat_map(|data| data.properties.iter())
+        .filter(|prop| {
+          let mime_type = prop.name.as_str();
+          mime_type.starts_with("text/") || SPLIT_OUTPUT_MIME_TYPES.contains(&mime_type)
+        })
+        .map(|prop| &prop.value),
+    );
+    for value in values {
+      text_changes.extend(get_multiline_string_text_change(
+        file_text,
+        value,
+        config.output_text_style,
+        config,
+        new_line,
+      ));
+    }
+  }
+  text_changes
+}
+
+/// Gets the text change that converts a string or an array of strings to the style.
+fn get_multiline_string_text_change(
+  file_text: &str,
+  value: &Value,
+  style: MultilineStringStyle,
+  config: &Configuration,
+  new_line: &str,
+) -> Option<TextChange> {
+  let text = match (value, style) {
+    (Value::StringLit(text), MultilineStringStyle::Array) => text.value.to_string(),
+    (Value::Array(lines), MultilineStringStyle::String) => {
+      let mut text = String::new();
+      for line in &lines.elements {
+        text.push_str(line.as_string_lit()?.value.as_ref());
+      }
+      text
+    }
+    _ => return None,
+  };
+  let new_text = match style {
+    MultilineStringStyle::Array => build_expanded_array_json_text(file_text, value.start(), &text, config, new_line),
+    _ => serde_json::to_string(&text).unwrap(),
+  };
+  Some(TextChange {
+    range: value.start()..value.end(),
+    new_text,
+  })
+}
+
+/// Gets the whitespace at the start of the line that contains the position.
+fn get_line_indent_text(file_text: &str, pos: usize) -> &str {
+  let line_start = file_text[..pos].rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+  let line_text = &file_text[line_start..pos];
+  &line_text[..line_text.len() - line_text.trim_start().len()]
+}
+
+fn get_indent_unit_text(config: &Configuration) -> String {
+  if config.use_tabs {
+    "\t".to_string()
+  } else {
+    " ".repeat(config.indent_width as usize)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::configuration::default_config;
+  use crate::text_changes::apply_text_changes;
+
+  #[test]
+  fn test_get_line_indent_text() {
+    assert_eq!(get_line_indent_text("  \"source\": [\"a\"]", 12), "  ");
+    assert_eq!(get_line_indent_text("{\n\t\"source\": []", 12), "\t");
+    assert_eq!(get_line_indent_text("[]", 0), "");
+  }
+
+  #[test]
+  fn converts_output_text() {
+    let text = r#"{
+ "outputs": [
+  {"name": "stdout", "output_type": "stream", "text": "a\nb"},
+  {
+   "data": {"image/png": "abc", "text/plain": ["1\n", "2"]},
+   "metadata": {},
+   "output_type": "display_data"
+  }
+ ]
+}"#;
+    let convert = |style: MultilineStringStyle| {
+      let mut config = default_config();
+      config.output_text_style = style;
+      let result = jsonc_parser::parse_to_ast(text, &Default::default(), &Default::default()).unwrap();
+      let value = result.value.unwrap();
+      let text_changes = get_output_text_changes(text, value.as_object().unwrap(), &config, "\n");
+      apply_text_changes(text, text_changes)
+    };
+    assert_eq!(
+      convert(MultilineStringStyle::Array),
+      r#"{
+ "outputs": [
+  {"name": "stdout", "output_type": "stream", "text": [
+   "a\n",
+   "b"
+  ]},
+  {
+   "data": {"image/png": "abc", "text/plain": ["1\n", "2"]},
+   "metadata": {},
+   "output_type": "display_data"
+  }
+ ]
+}"#
+    );
+    assert_eq!(
+      convert(MultilineStringStyle::String),
+      r#"{
+ "outputs": [
+  {"name": "stdout", "output_type": "stream", "text": "a\nb"},
+  {
+   "data": {"image/png": "abc", "text/plain": "1\n2"},
+   "metadata": {},
+   "output_type": "display_data"
+  }
+ ]
+}"#
+    );
+    assert_eq!(convert(MultilineStringStyle::Preserve), text);
+  }
+}
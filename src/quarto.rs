@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use crate::configuration::Configuration;
+use crate::fenced_code::parse_fenced_code_blocks;
+use crate::text_cells::TextCell;
+
+/// File extensions of Quarto and R Markdown documents.
+pub const QUARTO_EXTENSIONS: &[&str] = &["qmd", "rmd"];
+
+pub fn is_quarto_document(file_path: &Path) -> bool {
+  file_path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .is_some_and(|ext| QUARTO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Gets the code chunks of a Quarto or R Markdown document
+/// (ex. a block starting with ```` ```{python} ````).
+///
+/// The `#|` chunk option lines at the start of a chunk are not
+/// included in the cell.
+pub fn parse_quarto_document<'a>(text: &'a str, config: &Configuration) -> Vec<TextCell<'a>> {
+  if !config.format_code_cells {
+    return Vec::new();
+  }
+  parse_fenced_code_blocks(text)
+    .into_iter()
+    .filter_map(|block| Some((get_chunk_engine(block.info)?, block)))
+    .enumerate()
+    .filter_map(|(index, (engine, block))| {
+      let mut start = block.range.start;
+      for line in text[block.range.clone()].split_inclusive('\n') {
+        if !is_chunk_option_line(line) {
+          break;
+        }
+        start += line.len();
+      }
+      let range = start.min(block.range.end)..block.range.end;
+      if text[range.clone()].trim().is_empty() {
+        return None;
+      }
+      Some(TextCell {
+        index,
+        language: engine,
+        range,
+        line_prefix: (!block.indent.is_empty()).then(|| block.indent.to_string()),
+      })
+    })
+    .collect()
+}
+
+/// Gets the engine of an executable chunk from the fence's info string
+/// (ex. `python` for `{python}` or `r` for `{r setup, include=FALSE}`).
+fn get_chunk_engine(info: &str) -> Option<&str> {
+  let attributes = info.strip_prefix('{')?.trim_start();
+  let engine = attributes
+    .split(|c: char| c.is_whitespace() || c == ',' || c == '}')
+    .next()?;
+  // exclude code blocks with a class (`{.python}`) and raw blocks (`{=html}`)
+  if engine.is_empty() || engine.starts_with(['.', '=', '#']) {
+    None
+  } else {
+    Some(engine)
+  }
+}
+
+fn is_chunk_option_line(line: &str) -> bool {
+  let line = line.trim_start();
+  ["#|", "//|", "--|"].iter().any(|prefix| line.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn gets_chunk_engine() {
+    assert_eq!(get_chunk_engine("{python}"), Some("python"));
+    assert_eq!(get_chunk_engine("{r setup, include=FALSE}"), Some("r"));
+    assert_eq!(get_chunk_engine("{r, echo=FALSE}"), Some("r"));
+    assert_eq!(get_chunk_engine("{ sql }"), Some("sql"));
+    assert_eq!(get_chunk_engine("{.python}"), None);
+    assert_eq!(get_chunk_engine("{=html}"), None);
+    assert_eq!(get_chunk_engine("python"), None);
+  }
+}
@@ -0,0 +1,195 @@
+//! Maps positions between a cell's source and the string literals
+//! in the notebook it was read from.
+
+use std::ops::Range;
+
+/// A one-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineAndColumn {
+  pub line: usize,
+  pub column: usize,
+}
+
+/// Maps a position in a cell's source to the position in the notebook
+/// using the ranges of the string literals the source was read from.
+pub fn get_notebook_position(
+  file_text: &str,
+  literal_ranges: &[Range<usize>],
+  source_position: LineAndColumn,
+) -> Option<LineAndColumn> {
+  let mut line = 1;
+  let mut column = 1;
+  let mut last_literal_end = None;
+  for range in literal_ranges {
+    // exclude the quotes
+    let start = range.start + 1;
+    let end = range.end - 1;
+    let literal_text = &file_text[start..end];
+    let mut offset = 0;
+    while offset < literal_text.len() {
+      if line == source_position.line && column == source_position.column {
+        return Some(offset_to_line_and_column(file_text, start + offset));
+      }
+      let (c, raw_len) = decode_json_string_char(&literal_text[offset..]);
+      if c == '\n' {
+        line += 1;
+        column = 1;
+      } else {
+        column += 1;
+      }
+      offset += raw_len;
+    }
+    last_literal_end = Some(end);
+  }
+  // the position may be at the very end of the source
+  if line == source_position.line && column == source_position.column {
+    last_literal_end.map(|end| offset_to_line_and_column(file_text, end))
+  } else {
+    None
+  }
+}
+
+/// Maps a byte offset in the notebook to the byte offset in a cell's source.
+///
+/// Offsets before, between or after the string literals are moved to
+/// the closest position in the source.
+pub fn get_source_offset(file_text: &str, literal_ranges: &[Range<usize>], notebook_offset: usize) -> usize {
+  let mut source_offset = 0;
+  for range in literal_ranges {
+    // exclude the quotes
+    let start = range.start + 1;
+    let end = range.end - 1;
+    if notebook_offset <= start {
+      return source_offset;
+    }
+    let literal_text = &file_text[start..end];
+    let mut offset = 0;
+    while offset < literal_text.len() && start + offset < notebook_offset {
+      let (c, raw_len) = decode_json_string_char(&literal_text[offset..]);
+      source_offset += c.len_utf8();
+      offset += raw_len;
+    }
+    if notebook_offset < end {
+      return source_offset;
+    }
+  }
+  source_offset
+}
+
+/// Decodes the next character of a json string literal's text,
+/// returning the character along with the length of its raw text.
+fn decode_json_string_char(text: &str) -> (char, usize) {
+  let mut chars = text.chars();
+  let c = chars.next().unwrap();
+  if c != '\\' {
+    return (c, c.len_utf8());
+  }
+  match chars.next() {
+    Some('n') => ('\n', 2),
+    Some('r') => ('\r', 2),
+    Some('t') => ('\t', 2),
+    Some('b') => ('\u{8}', 2),
+    Some('f') => ('\u{c}', 2),
+    Some('u') => match parse_unicode_escape(text) {
+      Some(high @ 0xD800..=0xDBFF) => match parse_unicode_escape(&text[6..]) {
+        Some(low @ 0xDC00..=0xDFFF) => {
+          let code_point = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+          (char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER), 12)
+        }
+        _ => (char::REPLACEMENT_CHARACTER, 6),
+      },
+      Some(code_point) => (char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER), 6),
+      None => ('u', 2),
+    },
+    Some(c) => (c, 1 + c.len_utf8()),
+    None => ('\\', 1),
+  }
+}
+
+fn parse_unicode_escape(text: &str) -> Option<u32> {
+  let hex = text.strip_prefix("\\u")?.get(..4)?;
+  u32::from_str_radix(hex, 16).ok()
+}
+
+/// Gets the one-based line and column of a byte offset in the text.
+pub fn offset_to_line_and_column(text: &str, offset: usize) -> LineAndColumn {
+  let preceding_text = &text[..offset];
+  let line_start = preceding_text.rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+  LineAndColumn {
+    line: preceding_text.matches('\n').count() + 1,
+    column: preceding_text[line_start..].chars().count() + 1,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn position(line: usize, column: usize) -> LineAndColumn {
+    LineAndColumn { line, column }
+  }
+
+  fn get_literal_ranges(file_text: &str) -> Vec<Range<usize>> {
+    let parse_result = jsonc_parser::parse_to_ast(file_text, &Default::default(), &Default::default()).unwrap();
+    let root_value = parse_result.value.unwrap();
+    root_value
+      .as_object()
+      .unwrap()
+      .get_array("source")
+      .unwrap()
+      .elements
+      .iter()
+      .map(|element| {
+        let string_lit = element.as_string_lit().unwrap();
+        string_lit.range.start..string_lit.range.end
+      })
+      .collect()
+  }
+
+  #[test]
+  fn maps_source_position_to_notebook() {
+    let file_text = "{\n \"source\": [\n  \"a = 1\\n\",\n  \"b = \\\"\\u00e9\\\" +\"\n ]\n}";
+    let literal_ranges = get_literal_ranges(file_text);
+    assert_eq!(
+      get_notebook_position(file_text, &literal_ranges, position(1, 1)),
+      Some(position(3, 4))
+    );
+    assert_eq!(
+      get_notebook_position(file_text, &literal_ranges, position(2, 1)),
+      Some(position(4, 4))
+    );
+    // after the escaped quote and unicode escape
+    assert_eq!(
+      get_notebook_position(file_text, &literal_ranges, position(2, 8)),
+      Some(position(4, 18))
+    );
+    // end of the source
+    assert_eq!(
+      get_notebook_position(file_text, &literal_ranges, position(2, 10)),
+      Some(position(4, 20))
+    );
+    assert_eq!(get_notebook_position(file_text, &literal_ranges, position(3, 1)), None);
+  }
+
+  #[test]
+  fn maps_notebook_offset_to_source() {
+    let file_text = "{\"source\": [\"a = 1\\n\", \"b = \\\"\\u00e9\\\"\"]}";
+    let literal_ranges = get_literal_ranges(file_text);
+    let source = "a = 1\nb = \"\u{e9}\"";
+    // before the first literal
+    assert_eq!(get_source_offset(file_text, &literal_ranges, 0), 0);
+    // start of `a`
+    assert_eq!(get_source_offset(file_text, &literal_ranges, 13), 0);
+    // between the literals
+    assert_eq!(get_source_offset(file_text, &literal_ranges, 22), 6);
+    assert_eq!(&source[6..], "b = \"\u{e9}\"");
+    // after the unicode escape
+    let offset = file_text.find("\\u00e9").unwrap() + 6;
+    assert_eq!(&source[get_source_offset(file_text, &literal_ranges, offset)..], "\"");
+    // after the last literal
+    assert_eq!(
+      get_source_offset(file_text, &literal_ranges, file_text.len()),
+      source.len()
+    );
+  }
+}
@@ -0,0 +1,130 @@
+use jsonc_parser::ast::Object;
+use jsonc_parser::ast::Value;
+use jsonc_parser::common::Ranged;
+
+use crate::configuration::Configuration;
+use crate::text_changes::TextChange;
+
+/// Cell metadata written by Jupyter and its extensions that changes
+/// every time a notebook is run or viewed.
+const TRANSIENT_METADATA_KEYS: &[&str] = &["collapsed", "scrolled", "execution", "ExecuteTime"];
+
+/// Gets the text changes that clear a cell's outputs, execution
+/// count and transient metadata based on the configuration.
+pub fn get_strip_text_changes(cell: &Object, config: &Configuration) -> Vec<TextChange> {
+  let mut text_changes = Vec::new();
+  if config.strip_outputs
+    && let Some(outputs) = cell.get_array("outputs")
+    && !outputs.elements.is_empty()
+  {
+    text_changes.push(TextChange {
+      range: outputs.range.start..outputs.range.end,
+      new_text: "[]".to_string(),
+    });
+  }
+  if config.strip_execution_counts {
+    // nbformat 3 calls the execution count the prompt number
+    for name in ["execution_count", "prompt_number"] {
+      if let Some(prop) = cell.get(name)
+        && !matches!(prop.value, Value::NullKeyword(_))
+      {
+        text_changes.push(TextChange {
+          range: prop.value.start()..prop.value.end(),
+          new_text: "null".to_string(),
+        });
+      }
+    }
+  }
+  if config.strip_transient_metadata
+    && let Some(metadata) = cell.get_object("metadata")
+  {
+    text_changes.extend(get_remove_properties_text_changes(metadata, |name| {
+      TRANSIENT_METADATA_KEYS.contains(&name)
+    }));
+  }
+  text_changes
+}
+
+/// Gets the text changes that remove the matching properties
+/// along with their separating commas.
+fn get_remove_properties_text_changes(obj: &Object, should_remove: impl Fn(&str) -> bool) -> Vec<TextChange> {
+  let properties = &obj.properties;
+  let removed = properties
+    .iter()
+    .map(|prop| should_remove(prop.name.as_str()))
+    .collect::<Vec<_>>();
+  let Some(last_removed_index) = removed.iter().rposition(|r| *r) else {
+    return Vec::new();
+  };
+  let last_kept_index = removed.iter().rposition(|r| !*r);
+
+  let mut text_changes = Vec::new();
+  for (i, prop) in properties.iter().enumerate() {
+    if !removed[i] {
+      continue;
+    }
+    match last_kept_index {
+      // remove up to the next property, which includes the comma
+      Some(last_kept_index) if i < last_kept_index => text_changes.push(TextChange {
+        range: prop.range.start..properties[i + 1].range.start,
+        new_text: String::new(),
+      }),
+      // remove the trailing properties along with the comma after the last kept property
+      Some(last_kept_index) => {
+        text_changes.push(TextChange {
+          range: properties[last_kept_index].range.end..properties[last_removed_index].range.end,
+          new_text: String::new(),
+        });
+        break;
+      }
+      // remove everything within the braces
+      None => {
+        text_changes.push(TextChange {
+          range: obj.range.start + 1..obj.range.end - 1,
+          new_text: String::new(),
+        });
+        break;
+      }
+    }
+  }
+  text_changes
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::text_changes::apply_text_changes;
+
+  fn remove_properties(text: &str, names: &[&str]) -> String {
+    let result = jsonc_parser::parse_to_ast(text, &Default::default(), &Default::default()).unwrap();
+    let value = result.value.unwrap();
+    let text_changes = get_remove_properties_text_changes(value.as_object().unwrap(), |name| names.contains(&name));
+    apply_text_changes(text, text_changes)
+  }
+
+  #[test]
+  fn removes_properties() {
+    assert_eq!(
+      remove_properties("{\"a\": 1, \"b\": 2, \"c\": 3}", &["a"]),
+      "{\"b\": 2, \"c\": 3}"
+    );
+    assert_eq!(
+      remove_properties("{\"a\": 1, \"b\": 2, \"c\": 3}", &["b"]),
+      "{\"a\": 1, \"c\": 3}"
+    );
+    assert_eq!(
+      remove_properties("{\"a\": 1, \"b\": 2, \"c\": 3}", &["c"]),
+      "{\"a\": 1, \"b\": 2}"
+    );
+    assert_eq!(
+      remove_properties("{\n \"a\": 1,\n \"b\": 2,\n \"c\": 3\n}", &["a", "c"]),
+      "{\n \"b\": 2\n}"
+    );
+    assert_eq!(
+      remove_properties("{\"a\": 1, \"b\": 2, \"c\": 3}", &["b", "c"]),
+      "{\"a\": 1}"
+    );
+    assert_eq!(remove_properties("{ \"a\": 1, \"b\": 2 }", &["a", "b"]), "{}");
+    assert_eq!(remove_properties("{\"a\": 1}", &["b"]), "{\"a\": 1}");
+  }
+}
@@ -0,0 +1,212 @@
+use std::ops::Range;
+
+use crate::cell_error::CellFormatError;
+use crate::cell_error::combine_cell_errors;
+use crate::cell_error::find_error_position;
+use crate::configuration::CellErrorBehavior;
+use crate::configuration::Configuration;
+use crate::format_text::HostFormatRequest;
+use crate::format_text::convert_new_lines;
+use crate::format_text::format_code_block_text;
+use crate::format_text::has_ignore_comment;
+use crate::format_text::ranges_intersect;
+use crate::source_map::LineAndColumn;
+use crate::source_map::offset_to_line_and_column;
+use crate::text_changes::TextChange;
+use crate::text_changes::apply_text_changes;
+use anyhow::Result;
+
+/// A cell of a notebook stored as a text file (ex. a Jupytext percent script).
+pub struct TextCell<'a> {
+  /// Index of the cell in the file.
+  pub index: usize,
+  pub language: &'a str,
+  /// Range of the cell's source in the file text, excluding the final newline.
+  pub range: Range<usize>,
+  /// Text each line of the source is prefixed with in the file
+  /// (ex. `"# "` for markdown cells in percent scripts).
+  pub line_prefix: Option<String>,
+}
+
+/// Splits the text into lines, along with the offset each line starts at.
+pub fn get_lines(text: &str) -> Vec<(usize, &str)> {
+  let mut lines = Vec::new();
+  let mut offset = 0;
+  for line in text.split_inclusive('\n') {
+    lines.push((offset, line));
+    offset += line.len();
+  }
+  lines
+}
+
+/// Gets the range from the start of the first line to the
+/// end of the last line, excluding its newline.
+pub fn get_lines_range(lines: &[(usize, &str)]) -> Range<usize> {
+  let (start, _) = lines[0];
+  let (last_start, last_line) = lines[lines.len() - 1];
+  start..last_start + last_line.trim_end_matches(['\r', '\n']).len()
+}
+
+/// Gets the comment that starts the cell markers and
+/// commented out lines of a text file in the language.
+pub fn get_line_comment(language: &str) -> &'static str {
+  match language {
+    "c" | "cpp" | "csharp" | "dart" | "fsharp" | "go" | "java" | "javascript" | "kotlin" | "php" | "rust" | "scala"
+    | "swift" | "typescript" => "//",
+    "haskell" | "lua" | "sql" => "--",
+    _ => "#",
+  }
+}
+
+/// Formats the cells of a text file, leaving the text between them as-is.
+pub fn format_text_cells(
+  file_text: &str,
+  cells: &[TextCell],
+  range: Option<&Range<usize>>,
+  config: &Configuration,
+  mut format_with_host: impl FnMut(HostFormatRequest) -> Result<Option<String>>,
+) -> Result<Option<String>> {
+  let mut text_changes = Vec::new();
+  let mut cell_errors = Vec::new();
+  for cell in cells {
+    if range.is_some_and(|range| !ranges_intersect(range, &cell.range)) {
+      continue;
+    }
+    match format_text_cell(file_text, cell, range, config, &mut format_with_host) {
+      Ok(Some(new_text)) => text_changes.push(TextChange {
+        range: cell.range.clone(),
+        new_text,
+      }),
+      Ok(None) => {}
+      Err(err) => cell_errors.push(err),
+    }
+  }
+
+  if !cell_errors.is_empty() && config.on_cell_error == CellErrorBehavior::Error {
+    return Err(combine_cell_errors(cell_errors));
+  }
+
+  if text_changes.is_empty() {
+    Ok(None)
+  } else {
+    Ok(Some(apply_text_changes(file_text, text_changes)))
+  }
+}
+
+/// Formats a cell, returning its new text or `None` when it's unchanged.
+pub fn format_text_cell(
+  file_text: &str,
+  cell: &TextCell,
+  range: Option<&Range<usize>>,
+  config: &Configuration,
+  format_with_host: &mut impl FnMut(HostFormatRequest) -> Result<Option<String>>,
+) -> Result<Option<String>, CellFormatError> {
+  let cell_text = &file_text[cell.range.clone()];
+  // keep the newlines of the cell the same as before formatting
+  let new_line = if cell_text.contains("\r\n") { "\r\n" } else { "\n" };
+  let source = match &cell.line_prefix {
+    Some(line_prefix) => match remove_line_prefix(cell_text, line_prefix, new_line) {
+      Some(source) => source,
+      None => return Ok(None),
+    },
+    None => cell_text.to_string(),
+  };
+  if has_ignore_comment(&source) {
+    return Ok(None);
+  }
+  let source_range = match range {
+    // forward the range to the plugin when the selection is within the cell
+    Some(range) if cell.line_prefix.is_none() && cell.range.start <= range.start && range.end <= cell.range.end => {
+      Some(range.start - cell.range.start..range.end - cell.range.start)
+    }
+    _ => None,
+  };
+  let formatted_text = match format_code_block_text(cell.language, source, source_range, config, format_with_host) {
+    Ok(Some(formatted_text)) => formatted_text,
+    Ok(None) => return Ok(None),
+    Err(error) => {
+      let position = find_error_position(&format!("{:#}", error)).map(|position| {
+        let cell_start = offset_to_line_and_column(file_text, cell.range.start);
+        let prefix_len = cell
+          .line_prefix
+          .as_ref()
+          .map(|prefix| prefix.chars().count())
+          .unwrap_or(0);
+        let column_offset = if position.line == 1 { cell_start.column - 1 } else { 0 };
+        LineAndColumn {
+          line: cell_start.line + position.line - 1,
+          column: column_offset + prefix_len + position.column,
+        }
+      });
+      return Err(CellFormatError {
+        index: cell.index,
+        id: None,
+        language: cell.language.to_string(),
+        position,
+        error,
+      });
+    }
+  };
+  // the text after the cell is kept, so always trim the final newline
+  let formatted_text = formatted_text.trim_end();
+  let new_text = match &cell.line_prefix {
+    Some(line_prefix) => add_line_prefix(formatted_text, line_prefix, new_line),
+    None => convert_new_lines(formatted_text, new_line).unwrap_or_else(|| formatted_text.to_string()),
+  };
+  if new_text == cell_text {
+    Ok(None)
+  } else {
+    Ok(Some(new_text))
+  }
+}
+
+/// Removes the prefix from each line or returns `None` when a line isn't prefixed.
+fn remove_line_prefix(text: &str, line_prefix: &str, new_line: &str) -> Option<String> {
+  let mut lines = Vec::new();
+  for line in text.split('\n') {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    match line.strip_prefix(line_prefix) {
+      Some(line) => lines.push(line),
+      None if line.trim_end() == line_prefix.trim_end() => lines.push(""),
+      None => return None,
+    }
+  }
+  Some(lines.join(new_line))
+}
+
+fn add_line_prefix(text: &str, line_prefix: &str, new_line: &str) -> String {
+  text
+    .lines()
+    .map(|line| {
+      if line.is_empty() {
+        line_prefix.trim_end().to_string()
+      } else {
+        format!("{}{}", line_prefix, line)
+      }
+    })
+    .collect::<Vec<_>>()
+    .join(new_line)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn removes_and_adds_line_prefix() {
+    assert_eq!(
+      remove_line_prefix("# # Title\n#\n# Text", "# ", "\n").unwrap(),
+      "# Title\n\nText"
+    );
+    assert_eq!(remove_line_prefix("# Text\nx = 1", "# ", "\n"), None);
+    assert_eq!(add_line_prefix("# Title\n\nText", "# ", "\n"), "# # Title\n#\n# Text");
+    assert_eq!(
+      remove_line_prefix("# # Title\r\n#\r\n# Text", "# ", "\r\n").unwrap(),
+      "# Title\r\n\r\nText"
+    );
+    assert_eq!(
+      add_line_prefix("# Title\r\n\r\nText", "# ", "\r\n"),
+      "# # Title\r\n#\r\n# Text"
+    );
+  }
+}
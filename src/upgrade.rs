@@ -0,0 +1,257 @@
+use serde_json::Map;
+use serde_json::Value;
+use serde_json::json;
+
+use crate::canonical_json::to_canonical_json;
+
+/// Minor version of nbformat 4 that upgraded notebooks are written with.
+/// Later versions require cell ids.
+const UPGRADED_NBFORMAT_MINOR: u64 = 4;
+
+/// Upgrades an nbformat 3 notebook to nbformat 4 the same way as
+/// `nbformat.v4.upgrade`, returning the text of the upgraded notebook.
+///
+/// Returns `None` when the notebook isn't nbformat 3.
+pub fn upgrade_v3_notebook(text: &str) -> Option<String> {
+  let mut notebook = serde_json::from_str::<Value>(text).ok()?;
+  let notebook_obj = notebook.as_object_mut()?;
+  if notebook_obj.get("nbformat").and_then(|value| value.as_u64()) != Some(3) {
+    return None;
+  }
+
+  let mut cells = Vec::new();
+  if let Some(Value::Array(worksheets)) = notebook_obj.remove("worksheets") {
+    for mut worksheet in worksheets {
+      if let Some(Value::Array(worksheet_cells)) = worksheet.get_mut("cells").map(Value::take) {
+        cells.extend(worksheet_cells.into_iter().map(upgrade_cell));
+      }
+    }
+  }
+  notebook_obj.insert("cells".to_string(), Value::Array(cells));
+  notebook_obj.insert("nbformat".to_string(), json!(4));
+  notebook_obj.insert("nbformat_minor".to_string(), json!(UPGRADED_NBFORMAT_MINOR));
+  let metadata = get_or_insert_object(notebook_obj, "metadata");
+  metadata.remove("name");
+  metadata.insert("orig_nbformat".to_string(), json!(3));
+
+  // write the notebook the same way as when normalizing it
+  let text = notebook.to_string();
+  let parse_result = jsonc_parser::parse_to_ast(&text, &Default::default(), &Default::default()).ok()?;
+  Some(to_canonical_json(&parse_result.value?))
+}
+
+fn upgrade_cell(mut cell: Value) -> Value {
+  let Some(cell_obj) = cell.as_object_mut() else {
+    return cell;
+  };
+  get_or_insert_object(cell_obj, "metadata");
+  match cell_obj.get("cell_type").and_then(|cell_type| cell_type.as_str()) {
+    Some("code") => {
+      cell_obj.remove("language");
+      if let Some(collapsed) = cell_obj.remove("collapsed") {
+        get_or_insert_object(cell_obj, "metadata").insert("collapsed".to_string(), collapsed);
+      }
+      let source = cell_obj.remove("input").unwrap_or_else(|| json!(""));
+      cell_obj.insert("source".to_string(), source);
+      let execution_count = cell_obj.remove("prompt_number").unwrap_or(Value::Null);
+      cell_obj.insert("execution_count".to_string(), execution_count);
+      let outputs = match cell_obj.remove("outputs") {
+        Some(Value::Array(outputs)) => outputs.into_iter().map(upgrade_output).collect(),
+        _ => Vec::new(),
+      };
+      cell_obj.insert("outputs".to_string(), Value::Array(outputs));
+    }
+    Some("heading") => {
+      let level = cell_obj.remove("level").and_then(|level| level.as_u64()).unwrap_or(1);
+      let source = get_multiline_text(cell_obj.get("source"));
+      let source = format!(
+        "{} {}",
+        "#".repeat(level as usize),
+        source.lines().collect::<Vec<_>>().join(" ")
+      );
+      cell_obj.insert("cell_type".to_string(), json!("markdown"));
+      cell_obj.insert("source".to_string(), to_multiline_value(&source));
+    }
+    Some("html") => {
+      cell_obj.insert("cell_type".to_string(), json!("markdown"));
+    }
+    _ => {}
+  }
+  cell
+}
+
+fn upgrade_output(mut output: Value) -> Value {
+  let Some(output_obj) = output.as_object_mut() else {
+    return output;
+  };
+  match output_obj
+    .get("output_type")
+    .and_then(|output_type| output_type.as_str())
+  {
+    Some("pyout") => {
+      output_obj.insert("output_type".to_string(), json!("execute_result"));
+      let execution_count = output_obj.remove("prompt_number").unwrap_or(Value::Null);
+      output_obj.insert("execution_count".to_string(), execution_count);
+      move_mime_data(output_obj);
+    }
+    Some("display_data") => move_mime_data(output_obj),
+    Some("pyerr") => {
+      output_obj.insert("output_type".to_string(), json!("error"));
+    }
+    Some("stream") => {
+      let name = output_obj.remove("stream").unwrap_or_else(|| json!("stdout"));
+      output_obj.insert("name".to_string(), name);
+    }
+    _ => {}
+  }
+  output
+}
+
+/// Moves the output's data from its short keys (ex. `png`) to a mime bundle.
+fn move_mime_data(output_obj: &mut Map<String, Value>) {
+  const MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("javascript", "application/javascript"),
+    ("jpeg", "image/jpeg"),
+    ("json", "application/json"),
+    ("latex", "text/latex"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("text", "text/plain"),
+  ];
+
+  let mut data = Map::new();
+  for (key, mime_type) in MIME_TYPES {
+    if let Some(value) = output_obj.remove(*key) {
+      let value = match (*key, value) {
+        ("json", Value::String(text)) => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        (_, value) => value,
+      };
+      data.insert(mime_type.to_string(), value);
+    }
+  }
+  let mime_keys = output_obj
+    .keys()
+    .filter(|key| key.contains('/'))
+    .cloned()
+    .collect::<Vec<_>>();
+  for key in mime_keys {
+    if let Some(value) = output_obj.remove(&key) {
+      data.insert(key, value);
+    }
+  }
+  output_obj.insert("data".to_string(), Value::Object(data));
+  get_or_insert_object(output_obj, "metadata");
+}
+
+fn get_or_insert_object<'a>(obj: &'a mut Map<String, Value>, key: &str) -> &'a mut Map<String, Value> {
+  let value = obj.entry(key).or_insert_with(|| Value::Object(Map::new()));
+  if !value.is_object() {
+    *value = Value::Object(Map::new());
+  }
+  value.as_object_mut().unwrap()
+}
+
+fn get_multiline_text(value: Option<&Value>) -> String {
+  match value {
+    Some(Value::String(text)) => text.clone(),
+    Some(Value::Array(lines)) => lines.iter().filter_map(|line| line.as_str()).collect(),
+    _ => String::new(),
+  }
+}
+
+fn to_multiline_value(text: &str) -> Value {
+  Value::Array(
+    text
+      .split_inclusive('\n')
+      .map(|line| Value::String(line.to_string()))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn upgrades_v3_notebook() {
+    let text = r##"{
+ "metadata": {"name": "test"},
+ "nbformat": 3,
+ "nbformat_minor": 0,
+ "worksheets": [
+  {
+   "cells": [
+    {"cell_type": "heading", "level": 2, "metadata": {}, "source": ["Title"]},
+    {
+     "cell_type": "code",
+     "collapsed": false,
+     "input": ["print(1)\n", "1"],
+     "language": "python",
+     "metadata": {},
+     "outputs": [
+      {"output_type": "stream", "stream": "stdout", "text": ["1\n"]},
+      {"metadata": {}, "output_type": "pyout", "prompt_number": 1, "text": ["1"]}
+     ],
+     "prompt_number": 1
+    }
+   ],
+   "metadata": {}
+  }
+ ]
+}"##;
+    assert_eq!(
+      upgrade_v3_notebook(text).unwrap(),
+      r###"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": [
+    "## Title"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {
+    "collapsed": false
+   },
+   "outputs": [
+    {
+     "name": "stdout",
+     "output_type": "stream",
+     "text": [
+      "1\n"
+     ]
+    },
+    {
+     "data": {
+      "text/plain": [
+       "1"
+      ]
+     },
+     "execution_count": 1,
+     "metadata": {},
+     "output_type": "execute_result"
+    }
+   ],
+   "source": [
+    "print(1)\n",
+    "1"
+   ]
+  }
+ ],
+ "metadata": {
+  "orig_nbformat": 3
+ },
+ "nbformat": 4,
+ "nbformat_minor": 4
+}
+"###
+    );
+    assert_eq!(upgrade_v3_notebook("{\"nbformat\": 4, \"cells\": []}"), None);
+  }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashSet;
+
+use jsonc_parser::ast::Array;
+use jsonc_parser::ast::Object;
+use jsonc_parser::ast::Value;
+use jsonc_parser::common::Ranged;
+
+use crate::cell_ids::is_valid_cell_id;
+use crate::source_map::LineAndColumn;
+use crate::source_map::offset_to_line_and_column;
+
+/// A place where a notebook doesn't match the nbformat 4 schema.
+pub struct SchemaViolation {
+  /// Path to the value (ex. `cells[1].source`).
+  pub path: String,
+  pub position: LineAndColumn,
+  pub message: String,
+}
+
+impl SchemaViolation {
+  /// Whether the violation is a missing, invalid or duplicate cell id.
+  pub fn is_cell_id_violation(&self) -> bool {
+    self.path.ends_with(".id") || self.message == "Missing required property \"id\"."
+  }
+}
+
+impl std::fmt::Display for SchemaViolation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if !self.path.is_empty() {
+      write!(f, "{} ", self.path)?;
+    }
+    write!(
+      f,
+      "at line {}, column {}: {}",
+      self.position.line, self.position.column, self.message
+    )
+  }
+}
+
+/// Combines the violations into a single error.
+pub fn combine_schema_violations(message: &str, violations: Vec<SchemaViolation>) -> anyhow::Error {
+  let violations = violations
+    .iter()
+    .map(|violation| format!("  {}", violation))
+    .collect::<Vec<_>>()
+    .join("\n");
+  anyhow::anyhow!("{}\n{}", message, violations)
+}
+
+/// Checks the notebook against the parts of the nbformat 4 schema that
+/// Jupyter relies on when opening a notebook.
+///
+/// Notebooks before nbformat 4 are not validated.
+pub fn validate_notebook_schema(file_text: &str, root_value: &Value) -> Vec<SchemaViolation> {
+  let mut validator = Validator {
+    file_text,
+    violations: Vec::new(),
+  };
+  validator.validate_root(root_value);
+  validator.violations
+}
+
+struct Validator<'a> {
+  file_text: &'a str,
+  violations: Vec<SchemaViolation>,
+}
+
+impl Validator<'_> {
+  fn validate_root(&mut self, root_value: &Value) {
+    let Some(root_obj) = self.expect_object("", root_value) else {
+      return;
+    };
+    match root_obj.get_number("nbformat").map(|nbformat| nbformat.value) {
+      Some("4") => {}
+      Some(nbformat) if nbformat.parse::<u64>().is_ok_and(|nbformat| nbformat < 4) => return,
+      _ => {
+        if let Some(prop) = root_obj.get("nbformat") {
+          self.add("nbformat", &prop.value, "Expected 4.");
+        }
+      }
+    }
+    self.validate_properties(root_obj, "", &["cells", "metadata", "nbformat", "nbformat_minor"]);
+    let nbformat_minor = match root_obj.get("nbformat_minor") {
+      Some(prop) => self.expect_integer("nbformat_minor", &prop.value),
+      None => None,
+    };
+    if let Some(prop) = root_obj.get("metadata") {
+      self.expect_object("metadata", &prop.value);
+    }
+    let Some(prop) = root_obj.get("cells") else {
+      return;
+    };
+    let Some(cells) = self.expect_array("cells", &prop.value) else {
+      return;
+    };
+    // cell ids were added in nbformat 4.5
+    let requires_id = nbformat_minor.is_some_and(|minor| minor >= 5);
+    let mut ids = HashSet::new();
+    for (index, cell) in cells.elements.iter().enumerate() {
+      let path = format!("cells[{}]", index);
+      let Some(cell) = self.expect_object(&path, cell) else {
+        continue;
+      };
+      self.validate_cell(&path, cell, requires_id, &mut ids);
+    }
+  }
+
+  fn validate_cell<'b>(&mut self, path: &str, cell: &'b Object, requires_id: bool, ids: &mut HashSet<&'b str>) {
+    let Some(cell_type_prop) = cell.get("cell_type") else {
+      self.expect_property(cell, path, "cell_type");
+      return;
+    };
+    let properties: &[&str] = match cell_type_prop.value.as_string_lit().map(|value| value.value.as_ref()) {
+      Some("code") => &["cell_type", "execution_count", "id", "metadata", "outputs", "source"],
+      Some("markdown") | Some("raw") => &["attachments", "cell_type", "id", "metadata", "source"],
+      _ => {
+        self.add(
+          &format!("{}.cell_type", path),
+          &cell_type_prop.value,
+          "Expected \"code\", \"markdown\" or \"raw\".",
+        );
+        return;
+      }
+    };
+    let is_code = properties.contains(&"outputs");
+    let required: &[&str] = if is_code {
+      &["cell_type", "execution_count", "metadata", "outputs", "source"]
+    } else {
+      &["cell_type", "metadata", "source"]
+    };
+    for name in required {
+      self.expect_property(cell, path, name);
+    }
+    for prop in &cell.properties {
+      if !properties.contains(&prop.name.as_str()) {
+        self.add(
+          &format!("{}.{}", path, prop.name.as_str()),
+          &prop.value,
+          "Unexpected property for this cell type.",
+        );
+      }
+    }
+
+    match cell.get("id") {
+      Some(prop) => {
+        let id_path = format!("{}.id", path);
+        if let Some(id) = self.expect_string(&id_path, &prop.value) {
+          if !is_valid_cell_id(id) {
+            self.add(
+              &id_path,
+              &prop.value,
+              "Expected 1 to 64 letters, digits, hyphens or underscores.",
+            );
+          } else if !ids.insert(id) {
+            self.add(&id_path, &prop.value, "Duplicate cell id.");
+          }
+        }
+      }
+      None if requires_id => self.expect_property(cell, path, "id"),
+      None => {}
+    }
+    if let Some(prop) = cell.get("metadata") {
+      self.expect_object(&format!("{}.metadata", path), &prop.value);
+    }
+    if let Some(prop) = cell.get("source") {
+      self.expect_multiline_string(&format!("{}.source", path), &prop.value);
+    }
+    if let Some(prop) = cell.get("execution_count")
+      && !matches!(prop.value, Value::NullKeyword(_))
+    {
+      self.expect_integer(&format!("{}.execution_count", path), &prop.value);
+    }
+    if let Some(prop) = cell.get("outputs") {
+      let outputs_path = format!("{}.outputs", path);
+      if let Some(outputs) = self.expect_array(&outputs_path, &prop.value) {
+        for (index, output) in outputs.elements.iter().enumerate() {
+          let path = format!("{}[{}]", outputs_path, index);
+          if let Some(output) = self.expect_object(&path, output) {
+            self.validate_output(&path, output);
+          }
+        }
+      }
+    }
+  }
+
+  fn validate_output(&mut self, path: &str, output: &Object) {
+    let Some(output_type_prop) = output.get("output_type") else {
+      self.expect_property(output, path, "output_type");
+      return;
+    };
+    let required: &[&str] = match output_type_prop.value.as_string_lit().map(|value| value.value.as_ref()) {
+      Some("execute_result") => &["data", "execution_count", "metadata"],
+      Some("display_data") => &["data", "metadata"],
+      Some("stream") => &["name", "text"],
+      Some("error") => &["ename", "evalue", "traceback"],
+      _ => {
+        self.add(
+          &format!("{}.output_type", path),
+          &output_type_prop.value,
+          "Expected \"execute_result\", \"display_data\", \"stream\" or \"error\".",
+        );
+        return;
+      }
+    };
+    for name in required {
+      self.expect_property(output, path, name);
+    }
+    if let Some(prop) = output.get("text") {
+      self.expect_multiline_string(&format!("{}.text", path), &prop.value);
+    }
+  }
+
+  fn validate_properties(&mut self, obj: &Object, path: &str, names: &[&str]) {
+    for name in names {
+      self.expect_property(obj, path, name);
+    }
+    for prop in &obj.properties {
+      if !names.contains(&prop.name.as_str()) {
+        self.add(
+          &join_path(path, prop.name.as_str()),
+          &prop.value,
+          "Unexpected property.",
+        );
+      }
+    }
+  }
+
+  fn expect_property(&mut self, obj: &Object, path: &str, name: &str) {
+    if obj.get(name).is_none() {
+      self.add_at(
+        path,
+        obj.range.start,
+        format!("Missing required property \"{}\".", name),
+      );
+    }
+  }
+
+  fn expect_object<'b>(&mut self, path: &str, value: &'b Value<'b>) -> Option<&'b Object<'b>> {
+    let obj = value.as_object();
+    if obj.is_none() {
+      self.add(path, value, "Expected an object.");
+    }
+    obj
+  }
+
+  fn expect_array<'b>(&mut self, path: &str, value: &'b Value<'b>) -> Option<&'b Array<'b>> {
+    let array = value.as_array();
+    if array.is_none() {
+      self.add(path, value, "Expected an array.");
+    }
+    array
+  }
+
+  fn expect_string<'b>(&mut self, path: &str, value: &'b Value) -> Option<&'b str> {
+    let text = value.as_string_lit().map(|value| value.value.as_ref());
+    if text.is_none() {
+      self.add(path, value, "Expected a string.");
+    }
+    text
+  }
+
+  fn expect_integer(&mut self, path: &str, value: &Value) -> Option<u64> {
+    let number = match value {
+      Value::NumberLit(number) => number.value.parse::<u64>().ok(),
+      _ => None,
+    };
+    if number.is_none() {
+      self.add(path, value, "Expected a non-negative integer.");
+    }
+    number
+  }
+
+  /// Expects a string or an array of strings.
+  fn expect_multiline_string(&mut self, path: &str, value: &Value) {
+    match value {
+      Value::StringLit(_) => {}
+      Value::Array(lines) => {
+        for (index, line) in lines.elements.iter().enumerate() {
+          self.expect_string(&format!("{}[{}]", path, index), line);
+        }
+      }
+      _ => self.add(path, value, "Expected a string or an array of strings."),
+    }
+  }
+
+  fn add(&mut self, path: &str, value: &Value, message: &str) {
+    self.add_at(path, value.start(), message.to_string());
+  }
+
+  fn add_at(&mut self, path: &str, offset: usize, message: String) {
+    self.violations.push(SchemaViolation {
+      path: path.to_string(),
+      position: offset_to_line_and_column(self.file_text, offset),
+      message,
+    });
+  }
+}
+
+fn join_path(path: &str, name: &str) -> String {
+  if path.is_empty() {
+    name.to_string()
+  } else {
+    format!("{}.{}", path, name)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use jsonc_parser::CollectOptions;
+  use jsonc_parser::ParseOptions;
+
+  fn validate(text: &str) -> Vec<String> {
+    let parse_result = jsonc_parser::parse_to_ast(text, &CollectOptions::default(), &ParseOptions::default()).unwrap();
+    validate_notebook_schema(text, &parse_result.value.unwrap())
+      .iter()
+      .map(|violation| violation.to_string())
+      .collect()
+  }
+
+  #[test]
+  fn validates_notebook() {
+    assert_eq!(
+      validate(
+        r#"{
+ "cells": [
+  {"cell_type": "markdown", "id": "a", "metadata": {}, "source": ["Text"]},
+  {"cell_type": "code", "execution_count": null, "id": "b", "metadata": {}, "outputs": [], "source": "x = 1"}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"#
+      ),
+      Vec::<String>::new()
+    );
+    assert_eq!(
+      validate(
+        r#"{
+ "cells": [
+  {"cell_type": "markdown", "id": "a", "metadata": {}, "outputs": [], "source": [1]},
+  {"cell_type": "code", "id": "a", "metadata": {}, "outputs": [{"output_type": "stream"}], "source": ""},
+  {"cell_type": "raw", "id": "not valid", "source": ""},
+  {"cell_type": "code", "execution_count": null, "metadata": [], "outputs": [], "source": ""}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5,
+ "other": true
+}"#
+      ),
+      vec![
+        "other at line 11, column 11: Unexpected property.",
+        "cells[0].outputs at line 3, column 67: Unexpected property for this cell type.",
+        "cells[0].source[0] at line 3, column 82: Expected a string.",
+        "cells[1] at line 4, column 3: Missing required property \"execution_count\".",
+        "cells[1].id at line 4, column 31: Duplicate cell id.",
+        "cells[1].outputs[0] at line 4, column 64: Missing required property \"name\".",
+        "cells[1].outputs[0] at line 4, column 64: Missing required property \"text\".",
+        "cells[2] at line 5, column 3: Missing required property \"metadata\".",
+        "cells[2].id at line 5, column 30: Expected 1 to 64 letters, digits, hyphens or underscores.",
+        "cells[3] at line 6, column 3: Missing required property \"id\".",
+        "cells[3].metadata at line 6, column 62: Expected an object.",
+      ]
+    );
+    // nbformat 3 notebooks are not validated
+    assert_eq!(validate(r#"{"nbformat": 3, "worksheets": []}"#), Vec::<String>::new());
+  }
+}
@@ -1,5 +1,6 @@
 use super::configuration::Configuration;
 use super::configuration::resolve_config;
+use super::quarto::QUARTO_EXTENSIONS;
 
 use dprint_core::configuration::ConfigKeyMap;
 use dprint_core::configuration::GlobalConfiguration;
@@ -7,7 +8,6 @@ use dprint_core::generate_plugin_code;
 use dprint_core::plugins::CheckConfigUpdatesMessage;
 use dprint_core::plugins::ConfigChange;
 use dprint_core::plugins::FileMatchingInfo;
-use dprint_core::plugins::FormatRange;
 use dprint_core::plugins::FormatResult;
 use dprint_core::plugins::PluginInfo;
 use dprint_core::plugins::PluginResolveConfigurationResult;
@@ -24,11 +24,15 @@ impl SyncPluginHandler<Configuration> for JupyterPluginHandler {
     global_config: &GlobalConfiguration,
   ) -> PluginResolveConfigurationResult<Configuration> {
     let config = resolve_config(config, global_config);
+    let mut file_extensions = vec!["ipynb".to_string(), "dib".to_string()];
+    file_extensions.extend(QUARTO_EXTENSIONS.iter().map(|ext| ext.to_string()));
+    file_extensions.extend(config.config.jupytext_extensions.iter().cloned());
+    file_extensions.extend(config.config.databricks_extensions.iter().cloned());
     PluginResolveConfigurationResult {
       config: config.config,
       diagnostics: config.diagnostics,
       file_matching: FileMatchingInfo {
-        file_extensions: vec!["ipynb".to_string()],
+        file_extensions,
         file_names: vec![],
       },
     }
@@ -63,20 +67,26 @@ impl SyncPluginHandler<Configuration> for JupyterPluginHandler {
     _format_with_host: impl FnMut(SyncHostFormatRequest) -> FormatResult,
   ) -> FormatResult {
     let file_text = String::from_utf8(request.file_bytes)?;
-    super::format_text(&file_text, |path, text| {
-      let additional_config = ConfigKeyMap::new();
-      let request = SyncHostFormatRequest {
-        file_path: &path,
-        file_bytes: text.as_bytes(),
-        range: FormatRange::None,
-        override_config: &additional_config,
-      };
-      let maybe_bytes = format_with_host(request)?;
-      match maybe_bytes {
-        Some(bytes) => Ok(Some(String::from_utf8(bytes)?)),
-        None => Ok(None),
-      }
-    })
+    super::format_text(
+      request.file_path,
+      &file_text,
+      request.range,
+      request.config,
+      |request| {
+        let override_config = request.override_config.cloned().unwrap_or_default();
+        let request = SyncHostFormatRequest {
+          file_path: request.file_path,
+          file_bytes: request.file_text.as_bytes(),
+          range: request.range,
+          override_config: &override_config,
+        };
+        let maybe_bytes = format_with_host(request)?;
+        match maybe_bytes {
+          Some(bytes) => Ok(Some(String::from_utf8(bytes)?)),
+          None => Ok(None),
+        }
+      },
+    )
     .map(|maybe_file_text| maybe_file_text.map(|file_text| file_text.into_bytes()))
   }
 }
@@ -12,7 +12,7 @@ fn test_specs() {
   run_specs(
     &PathBuf::from("./tests/specs"),
     &ParseSpecOptions {
-      default_file_name: "file.py",
+      default_file_name: "file.ipynb",
     },
     &RunSpecsOptions {
       fix_failures: false,
@@ -20,38 +20,47 @@ fn test_specs() {
     },
     {
       let global_config = global_config.clone();
-      move |_file_path, file_text, spec_config| {
+      move |file_path, file_text, spec_config| {
         let spec_config: ConfigKeyMap = serde_json::from_value(spec_config.clone().into()).unwrap();
         let config_result = resolve_config(spec_config, &global_config);
         ensure_no_diagnostics(&config_result.diagnostics);
 
-        format_text(
-          /*file_path,*/ &file_text,
-          /*&config_result.config,*/
-          |path, text| {
-            if path.ends_with("code_block.py") {
-              if !text.ends_with("_python") {
-                Ok(Some(format!("{}_python", text)))
-              } else {
-                Ok(None)
-              }
-            } else if path.ends_with("code_block.md") {
-              if !text.ends_with("_markdown") {
-                Ok(Some(format!("{}_markdown", text)))
-              } else {
-                Ok(None)
-              }
-            } else if path.ends_with("code_block.ts") {
-              if !text.ends_with("_typescript") {
-                Ok(Some(format!("{}_typescript", text)))
-              } else {
-                Ok(None)
-              }
-            } else {
-              Ok(None)
-            }
-          },
-        )
+        format_text(file_path, file_text, None, &config_result.config, |request| {
+          let path = request.file_path;
+          let text = request.file_text;
+          if text.contains("syntax_error") {
+            return Err(anyhow::anyhow!("Syntax error."));
+          }
+          let language_suffix = if path.ends_with("code_block.py") {
+            "_python"
+          } else if path.ends_with("code_block.md") {
+            "_markdown"
+          } else if path.ends_with("code_block.ts") {
+            "_typescript"
+          } else {
+            return Ok(None);
+          };
+          // formatters commonly remove blank lines
+          if !text.is_empty() && text.trim().is_empty() {
+            return Ok(Some(String::new()));
+          }
+          // show the keys of the configuration sent to the plugin
+          let override_suffix = request
+            .override_config
+            .map(|override_config| {
+              override_config
+                .keys()
+                .map(|key| format!("_{}", key))
+                .collect::<String>()
+            })
+            .unwrap_or_default();
+          let suffix = format!("{}{}", language_suffix, override_suffix);
+          if text.ends_with(&suffix) {
+            Ok(None)
+          } else {
+            Ok(Some(format!("{}{}", text, suffix)))
+          }
+        })
       }
     },
     move |_file_path, _file_text, _spec_config| panic!("Plugin does not support dprint-core tracing."),